@@ -0,0 +1,674 @@
+//! ANSI escape sequence support.  Splits rendered text into runs of text
+//! sharing the same graphic rendition (SGR) so it can be converted into
+//! other output formats, and generates the sequences used to style templates
+
+use unicode_width::UnicodeWidthStr;
+
+/// A terminal color, either one of the 256 indexed colors (the first 16 being
+/// the standard and bright colors) or a 24-bit truecolor value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    /// Index into the 256-color palette
+    Indexed(u8),
+
+    /// 24-bit color
+    Rgb(u8, u8, u8),
+}
+
+/// RGB values for the 16 standard colors (xterm defaults)
+const BASIC_COLORS: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0xcd, 0x00, 0x00),
+    (0x00, 0xcd, 0x00),
+    (0xcd, 0xcd, 0x00),
+    (0x00, 0x00, 0xee),
+    (0xcd, 0x00, 0xcd),
+    (0x00, 0xcd, 0xcd),
+    (0xe5, 0xe5, 0xe5),
+    (0x7f, 0x7f, 0x7f),
+    (0xff, 0x00, 0x00),
+    (0x00, 0xff, 0x00),
+    (0xff, 0xff, 0x00),
+    (0x5c, 0x5c, 0xff),
+    (0xff, 0x00, 0xff),
+    (0x00, 0xff, 0xff),
+    (0xff, 0xff, 0xff),
+];
+
+/// Intensity of each step in the 6x6x6 color cube
+const CUBE_STEPS: [u8; 6] = [0x00, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+
+impl Color {
+    /// Returns the 24-bit value of this color
+    pub fn to_rgb(self) -> (u8, u8, u8) {
+        match self {
+            Color::Rgb(r, g, b) => (r, g, b),
+            Color::Indexed(i) if i < 16 => BASIC_COLORS[i as usize],
+            Color::Indexed(i) if i < 232 => {
+                let i = i - 16;
+                (
+                    CUBE_STEPS[(i / 36) as usize],
+                    CUBE_STEPS[((i / 6) % 6) as usize],
+                    CUBE_STEPS[(i % 6) as usize],
+                )
+            }
+            Color::Indexed(i) => {
+                let level = 8 + (i - 232) * 10;
+                (level, level, level)
+            }
+        }
+    }
+
+    /// Returns the closest color in the 256-color palette
+    pub fn to_indexed(self) -> Color {
+        let (r, g, b) = match self {
+            Color::Indexed(_) => return self,
+            Color::Rgb(r, g, b) => (r, g, b),
+        };
+
+        let nearest_step = |v: u8| -> u8 {
+            (0..6)
+                .min_by_key(|i| (CUBE_STEPS[*i as usize] as i32 - v as i32).abs())
+                .unwrap_or(0)
+        };
+
+        let cube =
+            Color::Indexed(16 + 36 * nearest_step(r) + 6 * nearest_step(g) + nearest_step(b));
+
+        let avg = (r as u32 + g as u32 + b as u32) / 3;
+        let gray = Color::Indexed(232 + ((avg.saturating_sub(8) + 5) / 10).min(23) as u8);
+
+        if distance(gray.to_rgb(), (r, g, b)) < distance(cube.to_rgb(), (r, g, b)) {
+            gray
+        } else {
+            cube
+        }
+    }
+
+    /// Returns the closest of the 16 standard colors
+    pub fn to_basic(self) -> Color {
+        if let Color::Indexed(i) = self {
+            if i < 16 {
+                return self;
+            }
+        }
+
+        let rgb = self.to_rgb();
+        let i = (0..16)
+            .min_by_key(|i| distance(BASIC_COLORS[*i], rgb))
+            .unwrap_or(0);
+
+        Color::Indexed(i as u8)
+    }
+
+    /// Returns this color as a CSS hex color (e.g., #ff0000)
+    pub fn to_hex(self) -> String {
+        let (r, g, b) = self.to_rgb();
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+
+    /// Parses a color from a template.  Accepts the names of the 16 standard
+    /// colors (e.g., `red`, `bright_red`), an index into the 256-color
+    /// palette (e.g., `208`) or a hex truecolor (e.g., `#ff8700` or `#f80`)
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Color to parse
+    pub fn parse(name: &str) -> Option<Color> {
+        const NAMES: [&str; 8] = [
+            "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+        ];
+
+        let name = name.trim().to_lowercase();
+        if let Some(hex) = name.strip_prefix('#') {
+            let digits: Vec<u8> = hex
+                .chars()
+                .map(|c| c.to_digit(16).map(|d| d as u8))
+                .collect::<Option<Vec<u8>>>()?;
+
+            return match digits.as_slice() {
+                [r, g, b] => Some(Color::Rgb(r * 17, g * 17, b * 17)),
+                [r1, r2, g1, g2, b1, b2] => {
+                    Some(Color::Rgb(r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2))
+                }
+                _ => None,
+            };
+        }
+
+        if let Ok(i) = name.parse::<u8>() {
+            return Some(Color::Indexed(i));
+        }
+
+        if name == "gray" || name == "grey" {
+            return Some(Color::Indexed(8));
+        }
+
+        let (bright, base) = match name.strip_prefix("bright_") {
+            Some(base) => (true, base),
+            None => (false, name.as_str()),
+        };
+
+        NAMES
+            .iter()
+            .position(|n| *n == base)
+            .map(|i| Color::Indexed(if bright { i as u8 + 8 } else { i as u8 }))
+    }
+
+    /// Returns the SGR parameters selecting this color as the foreground
+    /// (or background) color
+    ///
+    /// # Arguments
+    ///
+    /// * `background` - True to select the background color
+    fn sgr(self, background: bool) -> String {
+        let base = if background { 40 } else { 30 };
+        match self {
+            Color::Indexed(i) if i < 8 => format!("{}", base + i as u16),
+            Color::Indexed(i) if i < 16 => format!("{}", base + 60 + (i - 8) as u16),
+            Color::Indexed(i) => format!("{};5;{}", base + 8, i),
+            Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+        }
+    }
+}
+
+/// Returns the squared euclidean distance between two colors
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// The graphic rendition currently in effect
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reverse: bool,
+    pub strike: bool,
+}
+
+impl Style {
+    /// Returns true if no attributes are set
+    pub fn is_plain(&self) -> bool {
+        *self == Style::default()
+    }
+
+    /// Updates this style with the parameters of an SGR (`ESC [ ... m`)
+    /// sequence.  Unknown parameters are ignored
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - Semicolon separated parameters of the sequence
+    pub fn apply_sgr(&mut self, params: &[u16]) {
+        if params.is_empty() {
+            *self = Style::default();
+            return;
+        }
+
+        let mut i = 0;
+        while i < params.len() {
+            match params[i] {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.reverse = true,
+                9 => self.strike = true,
+                22 => {
+                    self.bold = false;
+                    self.dim = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.reverse = false,
+                29 => self.strike = false,
+                p @ 30..=37 => self.fg = Some(Color::Indexed((p - 30) as u8)),
+                p @ 90..=97 => self.fg = Some(Color::Indexed((p - 90 + 8) as u8)),
+                39 => self.fg = None,
+                p @ 40..=47 => self.bg = Some(Color::Indexed((p - 40) as u8)),
+                p @ 100..=107 => self.bg = Some(Color::Indexed((p - 100 + 8) as u8)),
+                49 => self.bg = None,
+                p @ 38 | p @ 48 => {
+                    let (color, used) = extended_color(&params[i + 1..]);
+                    if p == 38 {
+                        self.fg = color;
+                    } else {
+                        self.bg = color;
+                    }
+                    i += used;
+                }
+                _ => {}
+            }
+
+            i += 1;
+        }
+    }
+
+    /// Sets a single attribute (e.g., `bold` or `fg=red`) on this style
+    ///
+    /// # Arguments
+    ///
+    /// * `attr` - Attribute to set
+    pub fn set(&mut self, attr: &str) {
+        let (key, value) = match attr.find('=') {
+            Some(i) => (&attr[..i], Some(unquote(&attr[i + 1..]))),
+            None => (attr, None),
+        };
+
+        match (key, value) {
+            ("fg", Some(color)) => self.fg = Color::parse(color),
+            ("bg", Some(color)) => self.bg = Color::parse(color),
+            ("bold", _) => self.bold = true,
+            ("dim", _) => self.dim = true,
+            ("italic", _) => self.italic = true,
+            ("underline", _) => self.underline = true,
+            ("reverse", _) => self.reverse = true,
+            ("strike", _) => self.strike = true,
+            _ => {}
+        }
+    }
+
+    /// Applies all attributes set in `other` on top of this style
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - Style to apply
+    pub fn merge(&mut self, other: &Style) {
+        self.fg = other.fg.or(self.fg);
+        self.bg = other.bg.or(self.bg);
+        self.bold |= other.bold;
+        self.dim |= other.dim;
+        self.italic |= other.italic;
+        self.underline |= other.underline;
+        self.reverse |= other.reverse;
+        self.strike |= other.strike;
+    }
+
+    /// Returns the escape sequence that turns on this style, or an empty
+    /// string if no attributes are set
+    pub fn sgr(&self) -> String {
+        let mut params: Vec<String> = Vec::new();
+        let flags = [
+            (self.bold, "1"),
+            (self.dim, "2"),
+            (self.italic, "3"),
+            (self.underline, "4"),
+            (self.reverse, "7"),
+            (self.strike, "9"),
+        ];
+
+        for (set, param) in flags.iter() {
+            if *set {
+                params.push(param.to_string());
+            }
+        }
+
+        if let Some(fg) = self.fg {
+            params.push(fg.sgr(false));
+        }
+
+        if let Some(bg) = self.bg {
+            params.push(bg.sgr(true));
+        }
+
+        if params.is_empty() {
+            String::new()
+        } else {
+            format!("\x1b[{}m", params.join(";"))
+        }
+    }
+}
+
+/// Parses the color following a 38 or 48 SGR parameter, either `5;n` for
+/// an indexed color or `2;r;g;b` for a truecolor.  Returns the color and
+/// the number of parameters consumed
+fn extended_color(params: &[u16]) -> (Option<Color>, usize) {
+    match params {
+        [5, n, ..] => (Some(Color::Indexed(*n as u8)), 2),
+        [2, r, g, b, ..] => (Some(Color::Rgb(*r as u8, *g as u8, *b as u8)), 4),
+        _ => (None, params.len()),
+    }
+}
+
+/// Splits text containing ANSI escape sequences into runs of text and
+/// the style each run is drawn with.  SGR sequences update the style, all
+/// other escape sequences are dropped
+///
+/// # Arguments
+///
+/// * `text` - Text to split
+pub fn segments(text: &str) -> Vec<(Style, String)> {
+    let mut segments: Vec<(Style, String)> = Vec::new();
+    let mut style = Style::default();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            current.push(c);
+            continue;
+        }
+
+        // Only CSI (ESC [) sequences are understood, anything else is dropped
+        if chars.peek() != Some(&'[') {
+            chars.next();
+            continue;
+        }
+        chars.next();
+
+        let mut params = String::new();
+        let mut terminator = None;
+        for c in chars.by_ref() {
+            if ('\x40'..='\x7e').contains(&c) {
+                terminator = Some(c);
+                break;
+            }
+            params.push(c);
+        }
+
+        if terminator == Some('m') {
+            if !current.is_empty() {
+                segments.push((style, std::mem::take(&mut current)));
+            }
+
+            let params: Vec<u16> = params.split(';').map(|p| p.parse().unwrap_or(0)).collect();
+            style.apply_sgr(&params);
+        }
+    }
+
+    if !current.is_empty() {
+        segments.push((style, current));
+    }
+
+    segments
+}
+
+/// Resets all graphic rendition attributes
+pub const RESET: &str = "\x1b[0m";
+
+/// Removes a single pair of matching quotes surrounding a value, if present
+///
+/// # Arguments
+///
+/// * `value` - Value to unquote
+pub fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    for quote in ['"', '\''].iter() {
+        if value.len() >= 2 && value.starts_with(*quote) && value.ends_with(*quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+
+    value
+}
+
+/// Applies a style to text.  The style is turned on at the start of every
+/// line and reset at the end of it, so a style never bleeds into text drawn
+/// next to it (e.g., art in a neighboring column).  Styles already applied
+/// within the text take precedence, and the outer style is restored after
+/// each of them is reset
+///
+/// # Arguments
+///
+/// * `text` - Text to style
+/// * `style` - Style to apply
+pub fn paint(text: &str, style: &Style) -> String {
+    let start = style.sgr();
+    if start.is_empty() {
+        return text.to_string();
+    }
+
+    let restore = format!("{}{}", RESET, start);
+    text.split('\n')
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}{}", start, line.replace(RESET, &restore), RESET)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Rewrites every style in the text, e.g. to replace colors the terminal
+/// can't display.  Escape sequences are regenerated, so the output may not
+/// match the input byte for byte even if `f` changes nothing
+///
+/// # Arguments
+///
+/// * `text` - Text to rewrite
+/// * `f` - Function mapping each style to the style to draw with
+pub fn map_styles<F: Fn(Style) -> Style>(text: &str, f: F) -> String {
+    let mut s = String::new();
+    let mut current = Style::default();
+
+    for (style, run) in segments(text) {
+        let style = f(style);
+        if style != current {
+            if !current.is_plain() {
+                s.push_str(RESET);
+            }
+            s.push_str(&style.sgr());
+            current = style;
+        }
+
+        s.push_str(&run);
+    }
+
+    if !current.is_plain() {
+        s.push_str(RESET);
+    }
+
+    s
+}
+
+/// Removes all ANSI escape sequences from the text
+///
+/// # Arguments
+///
+/// * `text` - Text to strip
+pub fn strip(text: &str) -> String {
+    segments(text).into_iter().map(|(_, s)| s).collect()
+}
+
+/// Returns the number of terminal columns the text occupies.  Escape
+/// sequences take up no space
+///
+/// # Arguments
+///
+/// * `text` - Single line of text to measure
+pub fn width(text: &str) -> usize {
+    UnicodeWidthStr::width(strip(text).as_str())
+}
+
+/// Pads text with spaces to fill `width` columns, ignoring any escape
+/// sequences when measuring.  Text wider than `width` is left as-is
+///
+/// # Arguments
+///
+/// * `text` - Text to pad
+/// * `width` - Number of columns to fill
+/// * `right` - True to right-align the text (pad on the left)
+pub fn pad(text: &str, width: usize, right: bool) -> String {
+    let fill = " ".repeat(width.saturating_sub(self::width(text)));
+    if right {
+        format!("{}{}", fill, text)
+    } else {
+        format!("{}{}", text, fill)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sgr(params: &[u16]) -> Style {
+        let mut style = Style::default();
+        style.apply_sgr(params);
+        style
+    }
+
+    fn style(spec: &str) -> Style {
+        let mut style = Style::default();
+        for attr in spec.split_whitespace() {
+            style.set(attr);
+        }
+        style
+    }
+
+    #[test]
+    fn converts_colors_to_rgb() {
+        assert_eq!(Color::Indexed(9).to_rgb(), (0xff, 0x00, 0x00));
+        assert_eq!(Color::Indexed(16).to_rgb(), (0x00, 0x00, 0x00));
+        assert_eq!(Color::Indexed(196).to_rgb(), (0xff, 0x00, 0x00));
+        assert_eq!(Color::Indexed(232).to_rgb(), (8, 8, 8));
+        assert_eq!(Color::Indexed(255).to_rgb(), (238, 238, 238));
+        assert_eq!(Color::Rgb(1, 2, 3).to_hex(), "#010203");
+    }
+
+    #[test]
+    fn applies_sgr_parameters() {
+        let style = sgr(&[1, 3, 4, 32, 44]);
+        assert!(style.bold && style.italic && style.underline);
+        assert_eq!(style.fg, Some(Color::Indexed(2)));
+        assert_eq!(style.bg, Some(Color::Indexed(4)));
+
+        assert_eq!(sgr(&[91, 103]).fg, Some(Color::Indexed(9)));
+        assert_eq!(sgr(&[91, 103]).bg, Some(Color::Indexed(11)));
+        assert_eq!(sgr(&[38, 5, 208]).fg, Some(Color::Indexed(208)));
+        assert_eq!(sgr(&[48, 2, 1, 2, 3, 1]).bg, Some(Color::Rgb(1, 2, 3)));
+        assert!(sgr(&[48, 2, 1, 2, 3, 1]).bold);
+        assert!(sgr(&[1, 2, 22]).is_plain());
+        assert!(sgr(&[31, 1, 0]).is_plain());
+        assert!(sgr(&[31, 39]).is_plain());
+        assert!(sgr(&[]).is_plain());
+    }
+
+    #[test]
+    fn ignores_incomplete_extended_colors() {
+        assert_eq!(sgr(&[38, 2, 1]).fg, None);
+        assert_eq!(sgr(&[38]).fg, None);
+    }
+
+    #[test]
+    fn splits_text_into_segments() {
+        let red = sgr(&[31]);
+        assert_eq!(
+            segments("a\x1b[31mb\x1b[Kc\x1b[0md"),
+            vec![
+                (Style::default(), "a".to_string()),
+                (red, "bc".to_string()),
+                (Style::default(), "d".to_string()),
+            ]
+        );
+        assert_eq!(segments("\x1b[1m\x1b[0m"), vec![]);
+    }
+
+    #[test]
+    fn unquotes_values() {
+        assert_eq!(unquote(" \"a, b\" "), "a, b");
+        assert_eq!(unquote("'a'"), "a");
+        assert_eq!(unquote("\"a'"), "\"a'");
+        assert_eq!(unquote("\""), "\"");
+        assert_eq!(unquote("a"), "a");
+    }
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!(Color::parse("red"), Some(Color::Indexed(1)));
+        assert_eq!(Color::parse(" Bright_Cyan "), Some(Color::Indexed(14)));
+        assert_eq!(Color::parse("grey"), Some(Color::Indexed(8)));
+        assert_eq!(Color::parse("208"), Some(Color::Indexed(208)));
+        assert_eq!(Color::parse("#ff8700"), Some(Color::Rgb(0xff, 0x87, 0x00)));
+        assert_eq!(Color::parse("#F80"), Some(Color::Rgb(0xff, 0x88, 0x00)));
+        assert_eq!(Color::parse("#ff87"), None);
+        assert_eq!(Color::parse("#gg0000"), None);
+        assert_eq!(Color::parse("256"), None);
+        assert_eq!(Color::parse("bright_gray"), None);
+        assert_eq!(Color::parse("mauve"), None);
+    }
+
+    #[test]
+    fn sets_attributes() {
+        let set = style("fg=cyan bg='#002b36' bold underline sparkly fg2=red");
+        assert_eq!(set.fg, Some(Color::Indexed(6)));
+        assert_eq!(set.bg, Some(Color::Rgb(0x00, 0x2b, 0x36)));
+        assert!(set.bold && set.underline);
+        assert!(!set.italic && !set.dim);
+        assert!(style("fg=nope").is_plain());
+    }
+
+    #[test]
+    fn merges_styles() {
+        let mut merged = style("fg=red bg=blue bold");
+        merged.merge(&style("fg=green italic"));
+        assert_eq!(merged, style("fg=green bg=blue bold italic"));
+    }
+
+    #[test]
+    fn generates_sgr_sequences() {
+        assert_eq!(Style::default().sgr(), "");
+        assert_eq!(style("bold fg=red bg=bright_blue").sgr(), "\x1b[1;31;104m");
+        assert_eq!(
+            style("fg=208 bg=#010203").sgr(),
+            "\x1b[38;5;208;48;2;1;2;3m"
+        );
+        assert_eq!(sgr(&[4, 9, 2, 7, 3]).sgr(), "\x1b[2;3;4;7;9m");
+    }
+
+    #[test]
+    fn paints_each_line() {
+        let bold = style("bold");
+        assert_eq!(paint("a\n\nb", &bold), "\x1b[1ma\x1b[0m\n\n\x1b[1mb\x1b[0m");
+        assert_eq!(
+            paint("a\x1b[31mb\x1b[0mc", &bold),
+            "\x1b[1ma\x1b[31mb\x1b[0m\x1b[1mc\x1b[0m"
+        );
+        assert_eq!(paint("a", &Style::default()), "a");
+    }
+
+    #[test]
+    fn pads_by_visible_width() {
+        assert_eq!(width("\x1b[1mab\x1b[0m"), 2);
+        assert_eq!(width("日本"), 4);
+        assert_eq!(strip("\x1b[1mab\x1b[0m"), "ab");
+        assert_eq!(pad("\x1b[1mab\x1b[0m", 4, false), "\x1b[1mab\x1b[0m  ");
+        assert_eq!(pad("日本", 5, true), " 日本");
+        assert_eq!(pad("abc", 2, false), "abc");
+    }
+
+    #[test]
+    fn reduces_colors() {
+        assert_eq!(Color::Rgb(255, 0, 0).to_indexed(), Color::Indexed(196));
+        assert_eq!(Color::Rgb(0, 0x5f, 0xaf).to_indexed(), Color::Indexed(25));
+        assert_eq!(Color::Rgb(128, 128, 128).to_indexed(), Color::Indexed(244));
+        assert_eq!(Color::Indexed(5).to_indexed(), Color::Indexed(5));
+
+        assert_eq!(Color::Rgb(255, 0, 0).to_basic(), Color::Indexed(9));
+        assert_eq!(Color::Rgb(0, 0, 0).to_basic(), Color::Indexed(0));
+        assert_eq!(Color::Indexed(196).to_basic(), Color::Indexed(9));
+        assert_eq!(Color::Indexed(3).to_basic(), Color::Indexed(3));
+    }
+
+    #[test]
+    fn maps_styles() {
+        let text = "\x1b[1;31ma\x1b[22mb\x1b[0mc";
+        assert_eq!(
+            map_styles(text, |style| Style {
+                bold: false,
+                ..style
+            }),
+            "\x1b[31mab\x1b[0mc"
+        );
+        assert_eq!(map_styles(text, |_| Style::default()), "abc");
+        assert_eq!(
+            map_styles(text, |style| style),
+            "\x1b[1;31ma\x1b[0m\x1b[31mb\x1b[0mc"
+        );
+    }
+}
@@ -1,5 +1,7 @@
 //! All available commands (filters) that can be used to replaced captured information
 
+// Only some platforms still run external commands
+#[allow(unused_macros)]
 macro_rules! cmd {
     ($command:expr,$args:expr) => {{
         let mut cmd = std::process::Command::new($command);
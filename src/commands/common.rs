@@ -1,6 +1,5 @@
 //! All commands that will work on every operating system
 
-use crate::error::MotdResult;
 use std::{collections::HashMap, net::IpAddr};
 
 // Returns the IPs associated with this device
@@ -29,7 +28,7 @@ pub fn interfaces(
                         continue;
                     }
 
-                    addrs.push(format!("{}, ", ip));
+                    addrs.push(ip.to_string());
                 }
                 IpAddr::V6(ip6) => {}
             }
@@ -40,13 +39,3 @@ pub fn interfaces(
 
     map
 }
-
-/// Returns the hostname of this computer
-pub fn hostname(args: Option<String>) -> MotdResult<String> {
-    cmd!("hostname", args)
-}
-
-/// Returns the currently logged in user
-pub fn user(args: Option<String>) -> MotdResult<String> {
-    cmd!("whoami", args)
-}
@@ -2,8 +2,20 @@
 
 pub mod netlink;
 
+mod host;
+pub use host::*;
+
+mod mem;
+pub use mem::*;
+
 mod net;
 pub use net::*;
 
+mod process;
+pub use process::*;
+
 mod uptime;
 pub use uptime::*;
+
+mod utmp;
+pub use utmp::*;
@@ -0,0 +1,20 @@
+//! Commands to identify this machine and its current boot on Linux
+
+use crate::error::MotdResult;
+use std::fs;
+
+/// Reads the ID of this machine from `/etc/machine-id`, falling back to the
+/// copy D-Bus keeps in `/var/lib/dbus/machine-id`.  The ID is 32 lowercase
+/// hexadecimal characters, set when the system is installed
+pub fn machine_id() -> MotdResult<String> {
+    let id = fs::read_to_string("/etc/machine-id")
+        .or_else(|_| fs::read_to_string("/var/lib/dbus/machine-id"))?;
+    Ok(id.trim().to_string())
+}
+
+/// Reads the ID of the current boot from `/proc/sys/kernel/random/boot_id`.
+/// The kernel generates a new UUID every time the system boots
+pub fn boot_id() -> MotdResult<String> {
+    let id = fs::read_to_string("/proc/sys/kernel/random/boot_id")?;
+    Ok(id.trim().to_string())
+}
@@ -0,0 +1,148 @@
+//! Commands to get memory and CPU usage on Linux
+
+use crate::error::{MotdResult, ParsingError};
+use std::{fs, thread, time::Duration};
+
+/// How long to wait between the two samples used to measure CPU usage
+const CPU_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Reads `/proc/meminfo`, which lists the size of each memory pool in
+/// kilobytes, one per line.
+///
+/// Example:
+/// MemTotal:        8048024 kB
+/// MemFree:          306912 kB
+/// MemAvailable:    4620672 kB
+///
+/// Returns the total and available bytes of memory, followed by the total
+/// and free bytes of swap
+pub fn memory() -> MotdResult<(u64, u64, u64, u64)> {
+    parse_meminfo(&fs::read_to_string("/proc/meminfo")?)
+}
+
+/// Parses the contents of `/proc/meminfo`
+///
+/// # Arguments
+///
+/// * `contents` - Contents of `/proc/meminfo`
+fn parse_meminfo(contents: &str) -> MotdResult<(u64, u64, u64, u64)> {
+    let field = |name: &str| -> MotdResult<u64> {
+        let line = contents
+            .lines()
+            .find(|line| line.split(':').next() == Some(name))
+            .ok_or(ParsingError::NumberConversionFailed)?;
+
+        let kb: u64 = line
+            .split_whitespace()
+            .nth(1)
+            .ok_or(ParsingError::NumberConversionFailed)?
+            .parse()?;
+
+        Ok(kb * 1024)
+    };
+
+    Ok((
+        field("MemTotal")?,
+        field("MemAvailable")?,
+        field("SwapTotal")?,
+        field("SwapFree")?,
+    ))
+}
+
+/// Reads the busy and total time spent by all CPUs since boot from the
+/// first line of `/proc/stat`.
+///
+/// Example:
+/// cpu  10132153 290696 3084719 46828483 16683 0 25195 0 175628 0
+///
+/// The columns are user, nice, system, idle, iowait, irq, softirq, steal,
+/// guest and guest_nice.  Guest time is already counted in user and nice
+fn cpu_times() -> MotdResult<(u64, u64)> {
+    parse_cpu_times(&fs::read_to_string("/proc/stat")?)
+}
+
+/// Parses the busy and total CPU time from the contents of `/proc/stat`
+///
+/// # Arguments
+///
+/// * `contents` - Contents of `/proc/stat`
+fn parse_cpu_times(contents: &str) -> MotdResult<(u64, u64)> {
+    let line = contents
+        .lines()
+        .find(|line| line.starts_with("cpu "))
+        .ok_or(ParsingError::NumberConversionFailed)?;
+
+    let times = line
+        .split_whitespace()
+        .skip(1)
+        .take(8)
+        .map(str::parse::<u64>)
+        .collect::<Result<Vec<u64>, _>>()?;
+
+    let total: u64 = times.iter().sum();
+    let idle = times.get(3).ok_or(ParsingError::NumberConversionFailed)?
+        + times.get(4).copied().unwrap_or(0);
+
+    Ok((total - idle, total))
+}
+
+/// Samples `/proc/stat` twice, a short time apart, to measure CPU usage
+///
+/// Returns the percentage of time all CPUs were busy between the samples
+pub fn cpu_usage() -> MotdResult<f64> {
+    let (busy_before, total_before) = cpu_times()?;
+    thread::sleep(CPU_SAMPLE_INTERVAL);
+    let (busy_after, total_after) = cpu_times()?;
+
+    let total = total_after.saturating_sub(total_before);
+    if total == 0 {
+        return Ok(0.0);
+    }
+
+    Ok(busy_after.saturating_sub(busy_before) as f64 * 100.0 / total as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMINFO: &str = "MemTotal:        8048024 kB
+MemFree:          306912 kB
+MemAvailable:    4620672 kB
+SwapCached:          128 kB
+SwapTotal:       2097148 kB
+SwapFree:        2096124 kB
+";
+
+    #[test]
+    fn parses_meminfo() {
+        assert_eq!(
+            parse_meminfo(MEMINFO).unwrap(),
+            (
+                8048024 * 1024,
+                4620672 * 1024,
+                2097148 * 1024,
+                2096124 * 1024
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_incomplete_meminfo() {
+        let missing = MEMINFO.replace("MemAvailable", "MemAvail");
+        assert!(parse_meminfo(&missing).is_err());
+
+        let garbled = MEMINFO.replace("2097148", "lots");
+        assert!(parse_meminfo(&garbled).is_err());
+        assert!(parse_meminfo("SwapTotal:").is_err());
+    }
+
+    #[test]
+    fn parses_cpu_times() {
+        let stat = "cpu  100 20 30 400 50 6 7 8 9 10\ncpu0 1 2 3 4 5 6 7 8\n";
+        assert_eq!(parse_cpu_times(stat).unwrap(), (171, 621));
+        assert!(parse_cpu_times("cpu0 1 2 3 4").is_err());
+        assert!(parse_cpu_times("cpu  1 x 3 4").is_err());
+        assert!(parse_cpu_times("cpu  1 2 3").is_err());
+    }
+}
@@ -0,0 +1,17 @@
+//! Command to count the processes running on Linux
+
+use crate::error::MotdResult;
+use std::fs;
+
+/// Counts the processes running on the system, including those without a
+/// terminal (e.g., daemons), by counting the numbered directories in `/proc`
+pub fn process_count() -> MotdResult<usize> {
+    Ok(fs::read_dir("/proc")?
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            !name.is_empty() && name.bytes().all(|b| b.is_ascii_digit())
+        })
+        .count())
+}
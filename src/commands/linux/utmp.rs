@@ -0,0 +1,292 @@
+//! Commands to read the login accounting files on Linux
+
+use crate::{
+    commands::{c_field, process_alive, read_structs, LoginRecord},
+    error::MotdResult,
+};
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::Path,
+    ptr,
+};
+
+/// Login records of the users currently logged in
+pub const UTMP_PATH: &str = "/var/run/utmp";
+
+/// Login records of every login, logout and reboot since the file was
+/// last rotated
+pub const WTMP_PATH: &str = "/var/log/wtmp";
+
+/// Login records of every failed login attempt since the file was last
+/// rotated.  Only root and the utmp group may read it
+pub const BTMP_PATH: &str = "/var/log/btmp";
+
+/// Time and place of each user's most recent login, indexed by UID
+pub const LASTLOG_PATH: &str = "/var/log/lastlog";
+
+/// glibc's `struct lastlog`.  Like `struct utmp`, its timestamp is 32 bits
+/// wide on 64-bit architectures that support 32-bit programs
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct LastLog {
+    #[cfg(any(
+        target_arch = "aarch64",
+        target_arch = "s390x",
+        all(target_pointer_width = "32", not(target_arch = "x86_64"))
+    ))]
+    ll_time: libc::c_long,
+    #[cfg(not(any(
+        target_arch = "aarch64",
+        target_arch = "s390x",
+        all(target_pointer_width = "32", not(target_arch = "x86_64"))
+    )))]
+    ll_time: i32,
+    ll_line: [libc::c_char; 32],
+    ll_host: [libc::c_char; 256],
+}
+
+/// Reads every record from a utmp-formatted file.  The records are glibc's
+/// `struct utmp`, whose layout (e.g., the size of the timestamp) depends on
+/// the architecture, so it is taken from libc's identical `struct utmpx`
+///
+/// # Arguments
+///
+/// * `path` - Path to the file (e.g., `/var/run/utmp`)
+pub fn login_records<P: AsRef<Path>>(path: P) -> MotdResult<Vec<LoginRecord>> {
+    // utmpx is a C struct of integers and character arrays
+    let entries: Vec<libc::utmpx> = unsafe { read_structs(path)? };
+
+    Ok(entries
+        .iter()
+        .map(|entry| LoginRecord {
+            kind: entry.ut_type,
+            pid: entry.ut_pid,
+            line: c_field(&entry.ut_line),
+            user: c_field(&entry.ut_user),
+            host: c_field(&entry.ut_host),
+            time: i64::from(entry.ut_tv.tv_sec),
+            addr: address(entry.ut_addr_v6),
+        })
+        .collect())
+}
+
+/// Reads `/var/run/utmp`, returning a record for each session still open.
+/// Records left behind by sessions that ended without clearing them (e.g.,
+/// after a crash) are skipped
+pub fn sessions() -> MotdResult<Vec<LoginRecord>> {
+    Ok(login_records(UTMP_PATH)?
+        .into_iter()
+        .filter(|r| r.kind == libc::USER_PROCESS && !r.user.is_empty() && process_alive(r.pid))
+        .collect())
+}
+
+/// Reads `/var/log/wtmp`, returning every login, logout and reboot record,
+/// oldest first
+pub fn login_history() -> MotdResult<Vec<LoginRecord>> {
+    login_records(WTMP_PATH)
+}
+
+/// Reads `/var/log/btmp`, returning a record for every failed login attempt,
+/// oldest first
+pub fn failed_logins() -> MotdResult<Vec<LoginRecord>> {
+    login_records(BTMP_PATH)
+}
+
+/// Reads a user's most recent login from `/var/log/lastlog`, or None if they
+/// have never logged in.  The file is indexed by UID, so it is sparse and
+/// may appear enormous; only the user's own entry is read
+///
+/// # Arguments
+///
+/// * `uid` - UID of the user
+pub fn last_login(uid: u32) -> MotdResult<Option<LoginRecord>> {
+    read_lastlog(LASTLOG_PATH, uid)
+}
+
+/// Reads a user's entry from a lastlog-formatted file, or None if it is
+/// past the end of the file or empty
+///
+/// # Arguments
+///
+/// * `path` - Path to the file (e.g., `/var/log/lastlog`)
+/// * `uid` - UID of the user
+fn read_lastlog<P: AsRef<Path>>(path: P, uid: u32) -> MotdResult<Option<LoginRecord>> {
+    let size = mem::size_of::<LastLog>();
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(u64::from(uid) * size as u64))?;
+
+    // Users with higher UIDs than anyone who has logged in have no entry
+    let mut buf = vec![0u8; size];
+    match file.read_exact(&mut buf) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    // LastLog is a C struct of integers and character arrays
+    let entry: LastLog = unsafe { ptr::read_unaligned(buf.as_ptr() as *const LastLog) };
+    if entry.ll_time == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(LoginRecord {
+        kind: libc::USER_PROCESS,
+        pid: 0,
+        line: c_field(&entry.ll_line),
+        user: String::new(),
+        host: c_field(&entry.ll_host),
+        time: i64::from(entry.ll_time),
+        addr: None,
+    }))
+}
+
+/// Converts the address of a record's remote host.  The address is stored in
+/// network byte order, with only the first word set for IPv4 addresses
+///
+/// # Arguments
+///
+/// * `words` - Address as stored in the record
+fn address(words: [i32; 4]) -> Option<IpAddr> {
+    let mut bytes = [0u8; 16];
+    for (i, word) in words.iter().enumerate() {
+        bytes[i * 4..i * 4 + 4].copy_from_slice(&word.to_ne_bytes());
+    }
+
+    match words {
+        [0, 0, 0, 0] => None,
+        [_, 0, 0, 0] => Some(IpAddr::V4(Ipv4Addr::new(
+            bytes[0], bytes[1], bytes[2], bytes[3],
+        ))),
+        _ => Some(IpAddr::V6(Ipv6Addr::from(bytes))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::PathBuf, process, slice};
+
+    /// Returns a path in the temporary directory unique to the test
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("motd-utmp-{}-{}", process::id(), name))
+    }
+
+    /// Returns the bytes of a C struct, as it would be written to a file
+    fn bytes<T>(value: &T) -> &[u8] {
+        unsafe { slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+    }
+
+    /// Copies a string into a fixed-size C string field
+    fn set(field: &mut [libc::c_char], s: &str) {
+        for (c, b) in field.iter_mut().zip(s.bytes()) {
+            *c = b as libc::c_char;
+        }
+    }
+
+    fn utmpx(kind: i16, pid: i32, line: &str, user: &str, host: &str, time: i64) -> libc::utmpx {
+        let mut entry: libc::utmpx = unsafe { mem::zeroed() };
+        entry.ut_type = kind;
+        entry.ut_pid = pid;
+        set(&mut entry.ut_line, line);
+        set(&mut entry.ut_user, user);
+        set(&mut entry.ut_host, host);
+        entry.ut_tv.tv_sec = time as _;
+        entry
+    }
+
+    fn lastlog(time: i64, line: &str, host: &str) -> LastLog {
+        let mut entry: LastLog = unsafe { mem::zeroed() };
+        entry.ll_time = time as _;
+        set(&mut entry.ll_line, line);
+        set(&mut entry.ll_host, host);
+        entry
+    }
+
+    #[test]
+    fn reads_login_records() {
+        let mut login = utmpx(
+            libc::USER_PROCESS,
+            42,
+            "pts/0",
+            "alice",
+            "192.0.2.1",
+            1_700_000_000,
+        );
+        login.ut_addr_v6 = [i32::from_ne_bytes([192, 0, 2, 1]), 0, 0, 0];
+        let boot = utmpx(libc::BOOT_TIME, 0, "~", "reboot", "6.1.0", 1_600_000_000);
+
+        let mut data = bytes(&boot).to_vec();
+        data.extend_from_slice(bytes(&login));
+        // A record still being written is ignored
+        data.extend_from_slice(&bytes(&login)[..100]);
+
+        let path = temp_path("records");
+        fs::write(&path, &data).unwrap();
+        let records = login_records(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].kind, libc::BOOT_TIME);
+        assert_eq!(records[0].user, "reboot");
+        assert_eq!(records[0].host, "6.1.0");
+        assert_eq!(records[0].addr, None);
+
+        let record = &records[1];
+        assert_eq!(record.kind, libc::USER_PROCESS);
+        assert_eq!(record.pid, 42);
+        assert_eq!(record.line, "pts/0");
+        assert_eq!(record.user, "alice");
+        assert_eq!(record.host, "192.0.2.1");
+        assert_eq!(record.time, 1_700_000_000);
+        assert_eq!(record.addr, Some("192.0.2.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn converts_addresses() {
+        assert_eq!(address([0; 4]), None);
+        assert_eq!(
+            address([i32::from_ne_bytes([10, 0, 0, 1]), 0, 0, 0]),
+            Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)))
+        );
+
+        let ip: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let o = ip.octets();
+        let words = [
+            i32::from_ne_bytes([o[0], o[1], o[2], o[3]]),
+            i32::from_ne_bytes([o[4], o[5], o[6], o[7]]),
+            i32::from_ne_bytes([o[8], o[9], o[10], o[11]]),
+            i32::from_ne_bytes([o[12], o[13], o[14], o[15]]),
+        ];
+        assert_eq!(address(words), Some(IpAddr::V6(ip)));
+    }
+
+    #[test]
+    fn reads_lastlog_by_uid() {
+        let mut data = bytes(&lastlog(1_700_000_000, "tty1", "")).to_vec();
+        data.extend_from_slice(bytes(&lastlog(0, "", "")));
+        data.extend_from_slice(bytes(&lastlog(1_700_000_100, "pts/3", "example.com")));
+
+        let path = temp_path("lastlog");
+        fs::write(&path, &data).unwrap();
+        let entries: Vec<Option<LoginRecord>> = (0..4)
+            .map(|uid| read_lastlog(&path, uid).unwrap())
+            .collect();
+        fs::remove_file(&path).unwrap();
+
+        let first = entries[0].as_ref().unwrap();
+        assert_eq!((first.time, first.line.as_str()), (1_700_000_000, "tty1"));
+        assert_eq!(first.host, "");
+
+        // Never logged in, and past the end of the file
+        assert!(entries[1].is_none());
+        assert!(entries[3].is_none());
+
+        let third = entries[2].as_ref().unwrap();
+        assert_eq!(third.time, 1_700_000_100);
+        assert_eq!(third.line, "pts/3");
+        assert_eq!(third.host, "example.com");
+    }
+}
@@ -1,7 +1,19 @@
 //! Commands that are specific to mac OS X
 
+mod host;
+pub use host::*;
+
+mod mem;
+pub use mem::*;
+
 mod net;
 pub use net::*;
 
+mod process;
+pub use process::*;
+
 mod uptime;
 pub use uptime::*;
+
+mod utmp;
+pub use utmp::*;
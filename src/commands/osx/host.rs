@@ -0,0 +1,24 @@
+//! Commands to identify this machine and its current boot on Mac OS X
+
+use crate::error::{Error, MotdResult};
+
+/// Executes `ioreg -rd1 -c IOPlatformExpertDevice` and returns the
+/// platform's UUID, which identifies the machine
+///
+/// Example:
+/// "IOPlatformUUID" = "564D1C3A-8C3B-4F6E-9A2B-0D1E2F3A4B5C"
+pub fn machine_id() -> MotdResult<String> {
+    let output = cmd!("ioreg", Some("-rd1 -c IOPlatformExpertDevice"))?;
+    output
+        .lines()
+        .find(|line| line.contains("\"IOPlatformUUID\""))
+        .and_then(|line| line.split('=').nth(1))
+        .map(|id| id.trim().trim_matches('"').to_string())
+        .ok_or(Error::CommandFailed)
+}
+
+/// Executes `sysctl -n kern.bootsessionuuid`, which returns a UUID generated
+/// every time the system boots
+pub fn boot_id() -> MotdResult<String> {
+    cmd!("sysctl", Some("-n kern.bootsessionuuid"))
+}
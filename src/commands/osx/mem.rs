@@ -0,0 +1,58 @@
+//! Commands to get memory and CPU usage on a Mac OS X box
+
+use crate::error::{MotdResult, ParsingError};
+use regex::Regex;
+
+/// Executes `sysctl -n hw.memsize` for the total memory, `vm_stat` for
+/// the number of free, inactive and purgeable pages, and
+/// `sysctl -n vm.swapusage` for swap, which is formatted like:
+///
+/// total = 1024.00M  used = 266.75M  free = 757.25M  (encrypted)
+///
+/// Returns the total and available bytes of memory, followed by the total
+/// and free bytes of swap
+pub fn memory() -> MotdResult<(u64, u64, u64, u64)> {
+    let total: u64 = cmd!("sysctl", Some("-n hw.memsize"))?.parse()?;
+
+    let vm_stat = cmd!("vm_stat", None::<&str>)?;
+    let page_size: u64 = Regex::new(r"page size of (\d+) bytes")?
+        .captures(&vm_stat)
+        .ok_or(ParsingError::NumberConversionFailed)?[1]
+        .parse()?;
+
+    let pages = Regex::new(r"Pages (free|inactive|purgeable):\s+(\d+)")?;
+    let mut available = 0;
+    for caps in pages.captures_iter(&vm_stat) {
+        available += caps[2].parse::<u64>()? * page_size;
+    }
+
+    let swap = cmd!("sysctl", Some("-n vm.swapusage"))?;
+    let megabytes = |name: &str| -> MotdResult<u64> {
+        let re = Regex::new(&format!(r"{} = ([\d.]+)M", name))?;
+        let mb: f64 = re
+            .captures(&swap)
+            .and_then(|caps| caps[1].parse().ok())
+            .ok_or(ParsingError::NumberConversionFailed)?;
+
+        Ok((mb * 1024.0 * 1024.0) as u64)
+    };
+
+    Ok((total, available, megabytes("total")?, megabytes("free")?))
+}
+
+/// Executes `ps -A -o %cpu=`, which lists the CPU usage of every process
+/// as a percentage of a single core, and divides the sum by the number of
+/// cores from `sysctl -n hw.ncpu`
+///
+/// Returns the percentage of time all CPUs are busy
+pub fn cpu_usage() -> MotdResult<f64> {
+    let cores: f64 = cmd!("sysctl", Some("-n hw.ncpu"))?.parse::<u32>()?.into();
+
+    let output = cmd!("ps", Some("-A -o %cpu="))?;
+    let used: f64 = output
+        .split_whitespace()
+        .filter_map(|pct| pct.parse::<f64>().ok())
+        .sum();
+
+    Ok((used / cores).min(100.0))
+}
@@ -0,0 +1,11 @@
+//! Command to count the processes running on Mac OS X
+
+use crate::error::MotdResult;
+
+/// Executes `ps -e` to count the processes running on the system, including
+/// those without a terminal (e.g., daemons)
+pub fn process_count() -> MotdResult<usize> {
+    // The first line of output is the header
+    let output = cmd!("ps", Some("-e"))?;
+    Ok(output.lines().count().saturating_sub(1))
+}
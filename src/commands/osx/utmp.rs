@@ -0,0 +1,60 @@
+//! Commands to read the login accounting files on Mac OS X
+
+use crate::{
+    commands::{c_field, process_alive, read_structs, LoginRecord},
+    error::{Error, MotdResult},
+};
+use std::path::Path;
+
+/// Login records of the users currently logged in
+pub const UTMP_PATH: &str = "/var/run/utmpx";
+
+/// Reads every record from a utmpx-formatted file
+///
+/// # Arguments
+///
+/// * `path` - Path to the file (e.g., `/var/run/utmpx`)
+pub fn login_records<P: AsRef<Path>>(path: P) -> MotdResult<Vec<LoginRecord>> {
+    // utmpx is a C struct of integers and character arrays
+    let entries: Vec<libc::utmpx> = unsafe { read_structs(path)? };
+
+    Ok(entries
+        .iter()
+        .map(|entry| LoginRecord {
+            kind: entry.ut_type,
+            pid: entry.ut_pid,
+            line: c_field(&entry.ut_line),
+            user: c_field(&entry.ut_user),
+            host: c_field(&entry.ut_host),
+            time: entry.ut_tv.tv_sec as i64,
+            addr: None,
+        })
+        .collect())
+}
+
+/// Reads `/var/run/utmpx`, returning a record for each session still open
+pub fn sessions() -> MotdResult<Vec<LoginRecord>> {
+    Ok(login_records(UTMP_PATH)?
+        .into_iter()
+        .filter(|r| r.kind == libc::USER_PROCESS && !r.user.is_empty() && process_alive(r.pid))
+        .collect())
+}
+
+/// Login history is kept in the Apple System Log, which can't be read yet
+pub fn login_history() -> MotdResult<Vec<LoginRecord>> {
+    Err(Error::UnsupportedOS)
+}
+
+/// Failed logins are kept in the Apple System Log, which can't be read yet
+pub fn failed_logins() -> MotdResult<Vec<LoginRecord>> {
+    Err(Error::UnsupportedOS)
+}
+
+/// Last logins are kept in the Apple System Log, which can't be read yet
+///
+/// # Arguments
+///
+/// * `uid` - UID of the user
+pub fn last_login(_uid: u32) -> MotdResult<Option<LoginRecord>> {
+    Err(Error::UnsupportedOS)
+}
@@ -1,46 +1,527 @@
 //! Unix-family specific commands.  Any function in this file should run on ALL
 //! Unix variants (Mac OS X, Linux, FreeBSD, OpenBSD, etc)
 
-use std::{collections::HashSet, string::ToString};
+use crate::error::{Error, MotdResult};
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+    fs, io, mem,
+    net::IpAddr,
+    path::Path,
+    ptr,
+};
 
-/// Returns a list of logged in users
-pub fn users(args: Option<String>) -> HashSet<String> {
-    let mut set: HashSet<String> = HashSet::new();
+/// A record from a login accounting file (utmp, wtmp or utmpx)
+#[derive(Clone, Debug)]
+pub struct LoginRecord {
+    /// Type of record (e.g., `libc::USER_PROCESS`)
+    pub kind: i16,
 
-    let output = cmd!("users", args);
+    /// Process ID of the login process
+    pub pid: i32,
 
-    if let Ok(output) = output {
-        for user in output.split_whitespace() {
-            if !user.starts_with('#') {
-                set.insert(user.to_owned());
+    /// Terminal the session is on, without `/dev/` (e.g., `pts/0`)
+    pub line: String,
+
+    /// Name of the user logged in
+    pub user: String,
+
+    /// Remote host the user logged in from, empty for local logins
+    pub host: String,
+
+    /// UNIX timestamp of when the record was written
+    pub time: i64,
+
+    /// Address of the remote host, if recorded
+    pub addr: Option<IpAddr>,
+}
+
+/// Reads a file of fixed-size C structs, ignoring any partial struct at the
+/// end of the file (e.g., one that is still being written)
+///
+/// # Safety
+///
+/// `T` must be plain old data, valid for any bit pattern
+///
+/// # Arguments
+///
+/// * `path` - Path to the file to read
+pub unsafe fn read_structs<T: Copy, P: AsRef<Path>>(path: P) -> MotdResult<Vec<T>> {
+    let bytes = fs::read(path)?;
+    Ok(bytes
+        .chunks_exact(mem::size_of::<T>())
+        .map(|chunk| ptr::read_unaligned(chunk.as_ptr() as *const T))
+        .collect())
+}
+
+/// Converts a fixed-size, NUL-padded C string field to a String
+///
+/// # Arguments
+///
+/// * `chars` - Characters of the field
+pub fn c_field(chars: &[libc::c_char]) -> String {
+    let bytes: Vec<u8> = chars
+        .iter()
+        .take_while(|c| **c != 0)
+        .map(|c| *c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Returns the terminal connected to stdin or, failing that, stderr,
+/// without `/dev/` (e.g., `pts/0`).  Returns None if neither is a terminal
+pub fn tty() -> Option<String> {
+    [libc::STDIN_FILENO, libc::STDERR_FILENO]
+        .iter()
+        .find_map(|fd| {
+            let mut buf = [0 as libc::c_char; 256];
+            if unsafe { libc::ttyname_r(*fd, buf.as_mut_ptr(), buf.len()) } == 0 {
+                Some(c_field(&buf))
+            } else {
+                None
             }
+        })
+        .map(|path| path.trim_start_matches("/dev/").to_string())
+}
+
+/// Largest buffer offered to the reentrant passwd and group lookups
+const MAX_LOOKUP_BUFFER: usize = 1 << 20;
+
+/// An account from the passwd database
+#[derive(Clone, Debug)]
+pub struct Account {
+    /// Name of the user
+    pub name: String,
+
+    /// User ID
+    pub uid: u32,
+
+    /// ID of the user's primary group
+    pub gid: u32,
+
+    /// Comment field, usually the full name and contact details separated by
+    /// commas
+    pub gecos: String,
+
+    /// Home directory
+    pub home: String,
+
+    /// Login shell
+    pub shell: String,
+}
+
+impl Account {
+    /// Copies an account out of a passwd entry
+    ///
+    /// # Safety
+    ///
+    /// Every string in the entry must be NULL or point to a NUL-terminated
+    /// string
+    ///
+    /// # Arguments
+    ///
+    /// * `pwd` - Entry filled in by `getpwuid_r` or `getpwnam_r`
+    unsafe fn from_passwd(pwd: &libc::passwd) -> Account {
+        Account {
+            name: c_str(pwd.pw_name),
+            uid: pwd.pw_uid,
+            gid: pwd.pw_gid,
+            gecos: c_str(pwd.pw_gecos),
+            home: c_str(pwd.pw_dir),
+            shell: c_str(pwd.pw_shell),
         }
     }
+}
 
-    set
+/// Returns the account of the effective user, like `whoami`
+pub fn user() -> MotdResult<Account> {
+    account(unsafe { libc::geteuid() })?.ok_or(Error::CommandFailed)
 }
 
-/// Returns a list of processes
-pub fn processes(args: Option<&str>) -> Vec<String> {
-    let output = cmd!("ps", Some("au"));
+/// Looks up an account by UID, or None if there is no such user
+///
+/// # Arguments
+///
+/// * `uid` - User ID
+pub fn account(uid: u32) -> MotdResult<Option<Account>> {
+    lookup(|buf| {
+        let mut pwd: libc::passwd = unsafe { mem::zeroed() };
+        let mut result: *mut libc::passwd = ptr::null_mut();
+        let rc =
+            unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
 
-    if let Ok(output) = output {
-        output
-            .split_terminator("\n")
-            .map(ToString::to_string)
-            .collect()
-    } else {
-        Vec::new()
+        match rc {
+            0 if result.is_null() => Ok(None),
+            0 => Ok(Some(unsafe { Account::from_passwd(&pwd) })),
+            errno => Err(errno),
+        }
+    })
+}
+
+/// Looks up an account by name, or None if there is no such user
+///
+/// # Arguments
+///
+/// * `name` - Name of the user
+pub fn account_by_name(name: &str) -> MotdResult<Option<Account>> {
+    let c_name = CString::new(name).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    lookup(|buf| {
+        let mut pwd: libc::passwd = unsafe { mem::zeroed() };
+        let mut result: *mut libc::passwd = ptr::null_mut();
+        let rc = unsafe {
+            libc::getpwnam_r(
+                c_name.as_ptr(),
+                &mut pwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+
+        match rc {
+            0 if result.is_null() => Ok(None),
+            0 => Ok(Some(unsafe { Account::from_passwd(&pwd) })),
+            errno => Err(errno),
+        }
+    })
+}
+
+/// Returns the IDs and names of every group a user belongs to, starting
+/// with their primary group.  Groups without a name are named by their ID
+///
+/// # Arguments
+///
+/// * `name` - Name of the user
+/// * `gid` - ID of the user's primary group
+pub fn groups(name: &str, gid: u32) -> MotdResult<Vec<(u32, String)>> {
+    let c_name = CString::new(name).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let mut gids: Vec<libc::gid_t> = vec![0; 32];
+
+    loop {
+        let mut count = gids.len() as libc::c_int;
+        // Mac OS X declares groups as ints rather than gid_ts
+        let rc = unsafe {
+            libc::getgrouplist(
+                c_name.as_ptr(),
+                gid as _,
+                gids.as_mut_ptr() as *mut _,
+                &mut count,
+            )
+        };
+
+        if rc >= 0 {
+            gids.truncate(count.max(0) as usize);
+            break;
+        }
+
+        // glibc reports how many groups there are; others only that there
+        // are too many
+        let needed = (count.max(0) as usize).max(gids.len() * 2);
+        if needed > MAX_LOOKUP_BUFFER {
+            return Err(Error::CommandFailed);
+        }
+        gids.resize(needed, 0);
     }
+
+    // getgrouplist includes the primary group, but not always first
+    gids.retain(|g| *g != gid);
+    gids.insert(0, gid);
+    gids.dedup();
+
+    gids.into_iter()
+        .map(|gid| {
+            let name = group_name(gid)?.unwrap_or_else(|| gid.to_string());
+            Ok((gid, name))
+        })
+        .collect()
 }
 
-/// Runs the fortune command
-pub fn fortune(_args: Option<String>) -> String {
-    let output = cmd!("fortune", Some("-a"));
+/// Looks up the name of a group, or None if there is no such group
+///
+/// # Arguments
+///
+/// * `gid` - Group ID
+fn group_name(gid: u32) -> MotdResult<Option<String>> {
+    lookup(|buf| {
+        let mut grp: libc::group = unsafe { mem::zeroed() };
+        let mut result: *mut libc::group = ptr::null_mut();
+        let rc =
+            unsafe { libc::getgrgid_r(gid, &mut grp, buf.as_mut_ptr(), buf.len(), &mut result) };
+
+        match rc {
+            0 if result.is_null() => Ok(None),
+            0 => Ok(Some(unsafe { c_str(grp.gr_name) })),
+            errno => Err(errno),
+        }
+    })
+}
 
-    if let Ok(o) = output {
-        o.trim().to_string()
+/// Calls a reentrant lookup (e.g., `getpwuid_r`), growing its buffer until
+/// the entry fits
+///
+/// # Arguments
+///
+/// * `f` - Performs the lookup with a buffer, returning the entry found or
+///   the error number
+fn lookup<T, F>(mut f: F) -> MotdResult<Option<T>>
+where
+    F: FnMut(&mut [libc::c_char]) -> Result<Option<T>, libc::c_int>,
+{
+    let mut buf = vec![0 as libc::c_char; 1024];
+    loop {
+        match f(&mut buf) {
+            Ok(entry) => return Ok(entry),
+            Err(libc::ERANGE) if buf.len() < MAX_LOOKUP_BUFFER => {
+                let len = buf.len() * 2;
+                buf.resize(len, 0);
+            }
+            Err(errno) => return Err(io::Error::from_raw_os_error(errno).into()),
+        }
+    }
+}
+
+/// Copies a NUL-terminated C string, which may be NULL
+///
+/// # Safety
+///
+/// The pointer must be NULL or point to a NUL-terminated string
+///
+/// # Arguments
+///
+/// * `s` - String to copy
+unsafe fn c_str(s: *const libc::c_char) -> String {
+    if s.is_null() {
+        String::new()
     } else {
-        "No Fortune".to_string()
+        CStr::from_ptr(s).to_string_lossy().into_owned()
+    }
+}
+
+/// Returns true if a process is running, even if it belongs to another user
+///
+/// # Arguments
+///
+/// * `pid` - Process ID to check
+pub fn process_alive(pid: i32) -> bool {
+    pid > 0
+        && (unsafe { libc::kill(pid, 0) } == 0
+            || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM))
+}
+
+/// Returns the hostname of this computer from `gethostname`, falling back to
+/// the node name from `uname`.  Either may be fully qualified
+pub fn hostname() -> MotdResult<String> {
+    let mut buf = [0 as libc::c_char; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr(), buf.len()) } == 0 {
+        // The name may be truncated without a NUL if it doesn't fit
+        buf[buf.len() - 1] = 0;
+        let name = c_field(&buf);
+        if !name.is_empty() {
+            return Ok(name);
+        }
+    }
+
+    let mut uts: libc::utsname = unsafe { mem::zeroed() };
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    match c_field(&uts.nodename) {
+        name if name.is_empty() => Err(Error::CommandFailed),
+        name => Ok(name),
+    }
+}
+
+/// Resolves the fully qualified domain name of a host, like `hostname -f`.
+/// The canonical name of the host's entry in `/etc/hosts` is preferred,
+/// then the canonical name from `getaddrinfo` (e.g., from DNS).  Returns
+/// None if neither is fully qualified
+///
+/// # Arguments
+///
+/// * `hostname` - Name of the host, qualified or not
+pub fn fqdn(hostname: &str) -> Option<String> {
+    let short = hostname.split('.').next().unwrap_or(hostname);
+
+    // Each entry is an address, its canonical name, then any aliases
+    let hosts = fs::read_to_string("/etc/hosts").unwrap_or_default();
+    let from_hosts = hosts
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .find_map(|line| {
+            let mut names = line.split_whitespace().skip(1);
+            let canonical = names.next()?;
+            let matches = std::iter::once(canonical)
+                .chain(names)
+                .any(|name| name == hostname || name == short);
+            Some(canonical).filter(|c| matches && c.contains('.'))
+        });
+
+    if let Some(fqdn) = from_hosts {
+        return Some(fqdn.to_string());
+    }
+
+    let c_host = CString::new(hostname).ok()?;
+    let mut hints: libc::addrinfo = unsafe { mem::zeroed() };
+    hints.ai_flags = libc::AI_CANONNAME;
+    hints.ai_socktype = libc::SOCK_STREAM;
+
+    let mut res: *mut libc::addrinfo = ptr::null_mut();
+    if unsafe { libc::getaddrinfo(c_host.as_ptr(), ptr::null(), &hints, &mut res) } != 0 {
+        return None;
+    }
+
+    // Only the first result carries the canonical name
+    let canonical = unsafe { c_str((*res).ai_canonname) };
+    unsafe { libc::freeaddrinfo(res) };
+
+    Some(canonical).filter(|c| c.contains('.'))
+}
+
+/// Returns the total, free and available (to unprivileged users) number of
+/// bytes on the filesystem containing `path`
+///
+/// # Arguments
+///
+/// * `path` - Any path on the filesystem
+pub fn disk_usage(path: &str) -> MotdResult<(u64, u64, u64)> {
+    let c_path = CString::new(path).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+
+    let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    let frsize = stat.f_frsize as u64;
+    Ok((
+        stat.f_blocks as u64 * frsize,
+        stat.f_bfree as u64 * frsize,
+        stat.f_bavail as u64 * frsize,
+    ))
+}
+
+/// Reads the operating system identification from `/etc/os-release`,
+/// falling back to `/usr/lib/os-release`.  Each line is a shell-style
+/// assignment, with the value optionally quoted.
+///
+/// Example:
+/// ID=ubuntu
+/// ID_LIKE=debian
+/// PRETTY_NAME="Ubuntu 22.04.4 LTS"
+///
+/// Returns every variable in the file
+pub fn os_release() -> MotdResult<HashMap<String, String>> {
+    let contents = fs::read_to_string("/etc/os-release")
+        .or_else(|_| fs::read_to_string("/usr/lib/os-release"))?;
+
+    let mut vars = HashMap::new();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim();
+            let value = match value.chars().next() {
+                Some(q @ '"') | Some(q @ '\'') if value.len() >= 2 && value.ends_with(q) => {
+                    &value[1..value.len() - 1]
+                }
+                _ => value,
+            };
+
+            // Double-quoted values may escape $, ", \ and `
+            let mut unescaped = String::new();
+            let mut chars = value.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => unescaped.extend(chars.next()),
+                    c => unescaped.push(c),
+                }
+            }
+
+            vars.insert(key.trim().to_string(), unescaped);
+        }
+    }
+
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(s: &[u8]) -> Vec<libc::c_char> {
+        s.iter().map(|b| *b as libc::c_char).collect()
+    }
+
+    #[test]
+    fn reads_c_fields() {
+        assert_eq!(c_field(&field(b"pts/0\0\0\0")), "pts/0");
+        assert_eq!(c_field(&field(b"full")), "full");
+        assert_eq!(c_field(&field(b"\0junk")), "");
+        assert_eq!(c_field(&field(b"caf\xc3\xa9\0")), "caf\u{e9}");
+    }
+
+    #[test]
+    fn reads_whole_structs() {
+        let mut data = Vec::new();
+        for n in [1u32, 2, 3].iter() {
+            data.extend_from_slice(&n.to_ne_bytes());
+        }
+        data.extend_from_slice(&[4, 0]);
+
+        let path = std::env::temp_dir().join(format!("motd-structs-{}", std::process::id()));
+        fs::write(&path, &data).unwrap();
+        let structs: Vec<u32> = unsafe { read_structs(&path).unwrap() };
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(structs, vec![1, 2, 3]);
+        assert!(unsafe { read_structs::<u32, _>(&path) }.is_err());
+    }
+
+    #[test]
+    fn grows_lookup_buffer() {
+        let mut sizes = Vec::new();
+        let entry = lookup(|buf| {
+            sizes.push(buf.len());
+            if buf.len() < 5000 {
+                Err(libc::ERANGE)
+            } else {
+                Ok(Some(buf.len()))
+            }
+        })
+        .unwrap();
+
+        assert_eq!(entry, Some(8192));
+        assert_eq!(sizes, vec![1024, 2048, 4096, 8192]);
+    }
+
+    #[test]
+    fn limits_lookup_buffer() {
+        let mut largest = 0;
+        let entry: MotdResult<Option<()>> = lookup(|buf| {
+            largest = buf.len();
+            Err(libc::ERANGE)
+        });
+
+        assert!(entry.is_err());
+        assert_eq!(largest, MAX_LOOKUP_BUFFER);
+    }
+
+    #[test]
+    fn reports_lookup_errors() {
+        let entry: MotdResult<Option<()>> = lookup(|_| Err(libc::EIO));
+        assert!(entry.is_err());
+        assert!(lookup(|_| Ok(None::<()>)).unwrap().is_none());
+    }
+
+    #[test]
+    fn looks_up_root() {
+        let root = account(0).unwrap().unwrap();
+        assert_eq!(root.name, "root");
+        assert_eq!(root.gid, 0);
+        assert_eq!(account_by_name("root").unwrap().unwrap().uid, 0);
+        assert!(account_by_name("no such user").unwrap().is_none());
+        assert_eq!(groups("root", 0).unwrap()[0].0, 0);
     }
 }
@@ -0,0 +1,125 @@
+//! User configuration, loaded from a TOML file
+
+use crate::{error::MotdResult, fortune::Offensive, threshold::Threshold};
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::Path, path::PathBuf};
+
+/// Default location of the configuration file
+pub const CONFIG_PATH: &str = "/etc/motd-rs/config.toml";
+
+/// All user configurable settings.  Every section is optional
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Name of, or path to, the theme to render with
+    pub theme: Option<String>,
+
+    /// Settings for the scripting engine
+    pub script: ScriptConfig,
+
+    /// Settings for fortunes
+    pub fortune: FortuneConfig,
+
+    /// Thresholds applied to every use of a command, by command name
+    pub thresholds: HashMap<String, Threshold>,
+}
+
+/// Settings for scripts that define custom facts and formatting
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ScriptConfig {
+    /// Script files to load functions from, in addition to any
+    /// `{% script %}` blocks in the template
+    pub files: Vec<PathBuf>,
+
+    /// Maximum time, in milliseconds, a single function call may run
+    pub timeout_ms: u64,
+
+    /// Maximum number of operations a single function call may perform
+    pub max_operations: u64,
+
+    /// Maximum length of any string, in bytes
+    pub max_string_size: usize,
+
+    /// Maximum number of elements in any array
+    pub max_array_size: usize,
+
+    /// Maximum number of properties in any object map
+    pub max_map_size: usize,
+
+    /// Maximum depth of nested function calls
+    pub max_call_levels: usize,
+}
+
+impl Default for ScriptConfig {
+    fn default() -> ScriptConfig {
+        ScriptConfig {
+            files: Vec::new(),
+            timeout_ms: 250,
+            max_operations: 100_000,
+            max_string_size: 64 * 1024,
+            max_array_size: 4096,
+            max_map_size: 4096,
+            max_call_levels: 32,
+        }
+    }
+}
+
+/// Settings for choosing fortunes
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct FortuneConfig {
+    /// Directories (or individual files) to read fortunes from
+    pub dirs: Vec<PathBuf>,
+
+    /// Whether offensive fortunes are excluded, included or the only ones
+    /// chosen
+    pub offensive: Offensive,
+
+    /// Only choose fortunes no longer than this many bytes
+    pub max_length: Option<usize>,
+
+    /// Choose the same fortune all day, instead of a new one every time
+    pub daily: bool,
+}
+
+impl Default for FortuneConfig {
+    fn default() -> FortuneConfig {
+        FortuneConfig {
+            dirs: [
+                "/usr/share/games/fortunes",
+                "/usr/share/fortune",
+                "/usr/share/fortunes",
+                "/usr/local/share/games/fortunes",
+            ]
+            .iter()
+            .map(PathBuf::from)
+            .collect(),
+            offensive: Offensive::Include,
+            max_length: None,
+            daily: false,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration from a TOML file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the configuration file
+    pub fn load<P: AsRef<Path>>(path: P) -> MotdResult<Config> {
+        let contents = fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// Loads the configuration from the default location, falling back
+    /// to the default configuration if the file does not exist
+    pub fn load_default() -> MotdResult<Config> {
+        if Path::new(CONFIG_PATH).exists() {
+            Config::load(CONFIG_PATH)
+        } else {
+            Ok(Config::default())
+        }
+    }
+}
@@ -0,0 +1,494 @@
+//! Speech and thought bubbles spoken by a cow, drawn without `cowsay`.
+//!
+//! ```text
+//! {{ fortune | cowsay }}
+//! {{ fortune | cowsay(cow="tux", width=50) }}
+//! {{ fortune | cowthink(eyes="^^") }}
+//! ```
+//!
+//! Cows are read from standard `.cow` files, so any installed with
+//! `cowsay` can be used by name.  Cows in the cow directories take
+//! precedence over the bundled `default` and `tux`.  Text keeps its own line
+//! breaks and long lines are wrapped at word boundaries, never letting the
+//! bubble grow wider than the terminal.
+
+use crate::{
+    ansi,
+    error::{Error, MotdResult, ParsingError},
+    template,
+};
+use log::error;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use unicode_width::UnicodeWidthChar;
+
+/// Directories searched, in order, for a cow by name
+pub const COW_DIRS: [&str; 5] = [
+    "cows",
+    "/etc/motd-rs/cows",
+    "/usr/share/cowsay/cows",
+    "/usr/share/cows",
+    "/usr/local/share/cows",
+];
+
+/// Cow used when none is given
+pub const DEFAULT_COW: &str = "default";
+
+/// Default width of the text inside the bubble
+pub const DEFAULT_WIDTH: usize = 40;
+
+/// Cows bundled with motd-rs, used when not installed
+const BUNDLED_COWS: [(&str, &str); 2] = [
+    ("default", include_str!("../cows/default.cow")),
+    ("tux", include_str!("../cows/tux.cow")),
+];
+
+/// Columns the bubble's border adds around the text
+const BUBBLE_BORDER: usize = 4;
+
+/// A character that speaks, parsed from a `.cow` file
+#[derive(Clone, Debug)]
+pub struct Cow {
+    /// Art, with `$thoughts`, `$eyes` and `$tongue` left in place
+    pub art: String,
+
+    /// Whether the art is interpolated, i.e. its heredoc isn't single quoted
+    interpolate: bool,
+}
+
+/// How a cow says something
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Name of, or path to, the cow
+    pub cow: String,
+
+    /// Draw a thought bubble instead of a speech bubble
+    pub think: bool,
+
+    /// Eyes, two characters wide
+    pub eyes: String,
+
+    /// Tongue, two characters wide
+    pub tongue: String,
+
+    /// Maximum width of the text inside the bubble
+    pub width: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            cow: DEFAULT_COW.to_string(),
+            think: false,
+            eyes: "oo".to_string(),
+            tongue: "  ".to_string(),
+            width: DEFAULT_WIDTH,
+        }
+    }
+}
+
+impl Options {
+    /// Parses filter arguments, e.g. `cow="tux", width=50, think`
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments passed to the filter, exactly as written
+    pub fn parse(args: &str) -> Options {
+        let mut options = Options::default();
+        for arg in template::split_top_level(args, ',') {
+            let (key, value) = match arg.split_once('=') {
+                Some((key, value)) => (key.trim(), ansi::unquote(value.trim())),
+                None => (arg.trim(), ""),
+            };
+            match key {
+                "" => {}
+                "cow" | "f" => options.cow = value.to_string(),
+                "think" => options.think = true,
+                "eyes" | "e" => options.eyes = fit(value, 2),
+                "tongue" | "T" => options.tongue = fit(value, 2),
+                "width" | "W" => match value.parse::<usize>() {
+                    Ok(width) if width > 0 => options.width = width,
+                    _ => error!("invalid cowsay width {}", value),
+                },
+                key => error!("unknown cowsay option {}", key),
+            }
+        }
+        options
+    }
+}
+
+impl Cow {
+    /// Parses a `.cow` file.  Only the `$the_cow` heredoc is used; any other
+    /// Perl in the file is ignored
+    ///
+    /// # Arguments
+    ///
+    /// * `src` - Contents of the `.cow` file
+    pub fn parse(src: &str) -> MotdResult<Cow> {
+        let mut lines = src.lines();
+        let (terminator, interpolate) = lines
+            .by_ref()
+            .find_map(|line| {
+                let rest = line.trim_start().strip_prefix("$the_cow")?;
+                let rest = rest.trim_start().strip_prefix('=')?.trim_start();
+                let tag = rest.strip_prefix("<<")?.trim_end().trim_end_matches(';');
+                match tag.chars().next()? {
+                    q @ ('"' | '\'') => Some((tag.trim_matches(q).to_string(), q == '"')),
+                    _ => Some((tag.to_string(), true)),
+                }
+            })
+            .ok_or(Error::ParsingFailed(ParsingError::InvalidCow))?;
+
+        let mut art = Vec::new();
+        for line in lines {
+            if line == terminator {
+                return Ok(Cow {
+                    art: art.join("\n"),
+                    interpolate,
+                });
+            }
+            art.push(line);
+        }
+        Err(Error::ParsingFailed(ParsingError::InvalidCow))
+    }
+
+    /// Loads a cow from a `.cow` file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the `.cow` file
+    pub fn load<P: AsRef<Path>>(path: P) -> MotdResult<Cow> {
+        Cow::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Finds a cow, either by path, by name in one of the cow directories,
+    /// or by name among the bundled cows
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of, or path to, the cow
+    pub fn find(name: &str) -> MotdResult<Cow> {
+        if Path::new(name).is_file() {
+            return Cow::load(name);
+        }
+
+        let path = COW_DIRS
+            .iter()
+            .map(|dir| PathBuf::from(dir).join(format!("{}.cow", name)))
+            .find(|path| path.is_file());
+
+        if let Some(path) = path {
+            return Cow::load(path);
+        }
+
+        match BUNDLED_COWS.iter().find(|(cow, _)| *cow == name) {
+            Some((_, src)) => Cow::parse(src),
+            None => {
+                error!("cow {} not found", name);
+                Err(Error::CommandFailed)
+            }
+        }
+    }
+
+    /// Draws the cow, filling in its thoughts, eyes and tongue
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Options to draw the cow with
+    pub fn draw(&self, options: &Options) -> String {
+        if !self.interpolate {
+            return self.art.clone();
+        }
+
+        let thoughts = if options.think { "o" } else { "\\" };
+        let mut s = String::with_capacity(self.art.len());
+        let mut chars = self.art.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => s.extend(chars.next()),
+                '$' => {
+                    let braced = chars.peek() == Some(&'{');
+                    if braced {
+                        chars.next();
+                    }
+                    let mut name = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if !(c.is_ascii_alphanumeric() || c == '_') {
+                            break;
+                        }
+                        name.push(c);
+                        chars.next();
+                    }
+                    if braced && chars.peek() == Some(&'}') {
+                        chars.next();
+                    }
+
+                    match name.as_str() {
+                        "thoughts" => s.push_str(thoughts),
+                        "eyes" => s.push_str(&options.eyes),
+                        "tongue" => s.push_str(&options.tongue),
+                        // Other variables would be empty in Perl too
+                        "" => s.push('$'),
+                        _ => {}
+                    }
+                }
+                c => s.push(c),
+            }
+        }
+        s
+    }
+}
+
+/// Draws a cow saying, or thinking, some text
+///
+/// # Arguments
+///
+/// * `text` - Text to say; styles are removed
+/// * `options` - Options to draw the cow with
+/// * `columns` - Width of the terminal, if known
+pub fn say(text: &str, options: &Options, columns: Option<usize>) -> MotdResult<String> {
+    let cow = Cow::find(&options.cow)?;
+    let width = match columns {
+        Some(columns) => options
+            .width
+            .min(columns.saturating_sub(BUBBLE_BORDER).max(1)),
+        None => options.width,
+    };
+    let lines = wrap(&ansi::strip(text).replace('\t', "        "), width);
+    Ok(format!(
+        "{}\n{}",
+        bubble(&lines, options.think),
+        cow.draw(options)
+    ))
+}
+
+/// Draws a bubble around lines of text
+///
+/// # Arguments
+///
+/// * `lines` - Lines of text, already wrapped
+/// * `think` - Draw a thought bubble instead of a speech bubble
+fn bubble(lines: &[String], think: bool) -> String {
+    let width = lines.iter().map(|l| ansi::width(l)).max().unwrap_or(0);
+    let mut s = format!(" {}\n", "_".repeat(width + 2));
+
+    for (i, line) in lines.iter().enumerate() {
+        let (open, close) = match (think, lines.len(), i) {
+            (true, _, _) => ('(', ')'),
+            (false, 1, _) => ('<', '>'),
+            (false, _, 0) => ('/', '\\'),
+            (false, n, i) if i == n - 1 => ('\\', '/'),
+            _ => ('|', '|'),
+        };
+        s.push_str(&format!(
+            "{} {} {}\n",
+            open,
+            ansi::pad(line, width, false),
+            close
+        ));
+    }
+
+    s.push_str(&format!(" {}", "-".repeat(width + 2)));
+    s
+}
+
+/// Wraps text at word boundaries, keeping its own line breaks.  Words wider
+/// than the width are split
+///
+/// # Arguments
+///
+/// * `text` - Text to wrap, without styles
+/// * `width` - Maximum width of a line
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.trim_end().lines() {
+        let paragraph = paragraph.trim_end();
+        if ansi::width(paragraph) <= width {
+            lines.push(paragraph.to_string());
+            continue;
+        }
+
+        // Keep the paragraph's indentation on its first line, up to half
+        // the width
+        let indent = &paragraph[..paragraph.len() - paragraph.trim_start().len()];
+        let mut line = split_at_width(indent, width / 2).0.to_string();
+        for word in paragraph.split_whitespace() {
+            let mut word = word;
+            let line_width = ansi::width(&line);
+            let sep = usize::from(!line.trim().is_empty());
+
+            if line_width + sep + ansi::width(word) > width && !line.trim().is_empty() {
+                lines.push(std::mem::take(&mut line));
+            } else if sep == 1 {
+                line.push(' ');
+            }
+
+            while ansi::width(&line) + ansi::width(word) > width {
+                let (head, tail) = split_at_width(word, width - ansi::width(&line));
+                if head.is_empty() && line.trim().is_empty() {
+                    // Not even one character fits on an empty line
+                    break;
+                }
+                line.push_str(head);
+                lines.push(std::mem::take(&mut line));
+                word = tail;
+            }
+            line.push_str(word);
+        }
+        if !line.trim().is_empty() {
+            lines.push(line);
+        }
+    }
+
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines
+}
+
+/// Splits a word at the last character that fits in a width
+///
+/// # Arguments
+///
+/// * `word` - Word to split
+/// * `width` - Width the first part must fit in
+fn split_at_width(word: &str, width: usize) -> (&str, &str) {
+    let mut used = 0;
+    for (i, c) in word.char_indices() {
+        used += c.width().unwrap_or(0);
+        if used > width {
+            return word.split_at(i);
+        }
+    }
+    (word, "")
+}
+
+/// Pads or truncates a string to exactly so many characters, as `cowsay`
+/// does with eyes and tongues
+///
+/// # Arguments
+///
+/// * `s` - String to fit
+/// * `len` - Number of characters
+fn fit(s: &str, len: usize) -> String {
+    format!("{:<len$.len$}", s, len = len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_heredocs() {
+        let cow = Cow::parse("# comment\n$the_cow = <<\"EOC\";\n  $eyes\nEOC\n").unwrap();
+        assert_eq!(cow.art, "  $eyes");
+        assert!(cow.interpolate);
+
+        let cow = Cow::parse("$the_cow=<<EOC\nart\nEOC").unwrap();
+        assert_eq!(cow.art, "art");
+        assert!(cow.interpolate);
+
+        let cow = Cow::parse("$the_cow = <<'END';\n$eyes\nEND\n").unwrap();
+        assert_eq!(cow.art, "$eyes");
+        assert!(!cow.interpolate);
+    }
+
+    #[test]
+    fn rejects_invalid_cows() {
+        assert!(Cow::parse("").is_err());
+        assert!(Cow::parse("$cow = <<EOC;\nart\nEOC\n").is_err());
+        assert!(Cow::parse("$the_cow = <<EOC;\nart\n").is_err());
+    }
+
+    #[test]
+    fn draws_variables() {
+        let cow =
+            Cow::parse("$the_cow = <<EOC;\n$thoughts(${eyes})$tongue$$x\\\\\\$\nEOC").unwrap();
+        let mut options = Options {
+            tongue: "U ".to_string(),
+            ..Options::default()
+        };
+        assert_eq!(cow.draw(&options), "\\(oo)U $\\$");
+
+        options.think = true;
+        options.eyes = "^^".to_string();
+        assert_eq!(cow.draw(&options), "o(^^)U $\\$");
+    }
+
+    #[test]
+    fn draws_uninterpolated_art() {
+        let cow = Cow::parse("$the_cow = <<'EOC';\n$eyes \\\\\nEOC").unwrap();
+        assert_eq!(cow.draw(&Options::default()), "$eyes \\\\");
+    }
+
+    #[test]
+    fn draws_bundled_cows() {
+        for (name, _) in BUNDLED_COWS.iter() {
+            let art = Cow::find(name).unwrap().draw(&Options::default());
+            assert!(art.starts_with("   "));
+            assert!(!art.contains('$'));
+            assert!(!art.contains("\\\\"));
+        }
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = Options::parse(r#"cow="tux", think, eyes="xxx", tongue=U, width=20"#);
+        assert_eq!(options.cow, "tux");
+        assert!(options.think);
+        assert_eq!(options.eyes, "xx");
+        assert_eq!(options.tongue, "U ");
+        assert_eq!(options.width, 20);
+
+        let options = Options::parse("width=0");
+        assert_eq!(options.width, DEFAULT_WIDTH);
+    }
+
+    #[test]
+    fn wraps_text() {
+        assert_eq!(wrap("one two three", 8), vec!["one two", "three"]);
+        assert_eq!(wrap("one\n\ntwo\n", 8), vec!["one", "", "two"]);
+        assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("  indented text", 10), vec!["  indented", "text"]);
+        assert_eq!(wrap("", 4), vec![""]);
+
+        // Indentation is cut by width, not bytes
+        let indent = "\u{3000}".repeat(7);
+        let text = format!("{}{}", indent, "word ".repeat(12));
+        let lines = wrap(&text, 40);
+        assert_eq!(lines[0], format!("{}word word word word word", indent));
+        assert!(lines.iter().all(|line| ansi::width(line) <= 40));
+
+        let lines = wrap(&format!("{}a b c d e f g", indent), 8);
+        assert_eq!(lines[0], format!("{}a b", "\u{3000}".repeat(2)));
+        assert!(lines.iter().all(|line| ansi::width(line) <= 8));
+    }
+
+    #[test]
+    fn draws_bubbles() {
+        let one = vec!["hi".to_string()];
+        assert_eq!(bubble(&one, false), " ____\n< hi >\n ----");
+        assert_eq!(bubble(&one, true), " ____\n( hi )\n ----");
+
+        let three = vec!["a".to_string(), "bb".to_string(), "c".to_string()];
+        assert_eq!(
+            bubble(&three, false),
+            " ____\n/ a  \\\n| bb |\n\\ c  /\n ----"
+        );
+    }
+
+    #[test]
+    fn fits_bubble_to_terminal() {
+        let options = Options::default();
+        let said = say("one two three four", &options, Some(12)).unwrap();
+        let lines: Vec<&str> = said.lines().take(4).collect();
+        assert_eq!(
+            lines,
+            vec![" _________", "/ one two \\", "| three   |", "\\ four    /"]
+        );
+    }
+}
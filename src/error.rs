@@ -1,6 +1,7 @@
 //! Error representation for motd
 
 /// Represents different errors that can occur during execution of motd
+#[derive(Debug)]
 pub enum Error {
     /// The command that was executed failed
     CommandFailed,
@@ -10,15 +11,40 @@ pub enum Error {
 
     /// This command is not supported on the request OS
     UnsupportedOS,
+
+    /// Facts failed to serialize to the requested format
+    SerializationFailed,
+
+    /// A script failed to compile or a script function failed to run
+    ScriptFailed,
 }
 
 /// Represents errors that may occur while parsing text
+#[derive(Debug)]
 pub enum ParsingError {
     /// Regex failed to compile or in someother way panic'd
     RegexFailed,
 
     /// String failed to convert to a number
     NumberConversionFailed,
+
+    /// Configuration file is not valid TOML or has unknown values
+    InvalidConfig,
+
+    /// Template has unbalanced or unexpected block tags
+    InvalidTemplate,
+
+    /// FIGlet font file is missing its header or characters
+    InvalidFont,
+
+    /// Image file could not be decoded
+    InvalidImage,
+
+    /// Image has more pixels than motd will decode
+    ImageTooLarge,
+
+    /// Cow file has no `$the_cow` heredoc
+    InvalidCow,
 }
 
 /// Wrapper for a result struct
@@ -53,3 +79,33 @@ impl From<ParsingError> for Error {
         Error::ParsingFailed(e)
     }
 }
+
+impl From<serde_json::Error> for Error {
+    fn from(_: serde_json::Error) -> Error {
+        Error::SerializationFailed
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(_: serde_yaml::Error) -> Error {
+        Error::SerializationFailed
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(_: toml::ser::Error) -> Error {
+        Error::SerializationFailed
+    }
+}
+
+impl From<png::DecodingError> for Error {
+    fn from(_: png::DecodingError) -> Error {
+        Error::ParsingFailed(ParsingError::InvalidImage)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(_: toml::de::Error) -> Error {
+        Error::from(ParsingError::InvalidConfig)
+    }
+}
@@ -0,0 +1,234 @@
+//! Exports collected facts as metrics for monitoring systems
+
+use crate::{error::MotdResult, motd::Facts};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Escapes a label value per the Prometheus text exposition format
+///
+/// # Arguments
+///
+/// * `value` - Label value to escape
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Appends the HELP and TYPE lines for a gauge
+///
+/// # Arguments
+///
+/// * `out` - Buffer to append to
+/// * `name` - Name of the metric
+/// * `help` - Description of the metric
+fn gauge(out: &mut String, name: &str, help: &str) {
+    out.push_str(&format!("# HELP {} {}\n", name, help));
+    out.push_str(&format!("# TYPE {} gauge\n", name));
+}
+
+/// Formats facts in the Prometheus text exposition format
+///
+/// # Arguments
+///
+/// * `facts` - Facts to export
+pub fn prometheus(facts: &Facts) -> String {
+    let mut out = String::new();
+
+    gauge(
+        &mut out,
+        "motd_uptime_seconds",
+        "Number of seconds since the system booted",
+    );
+    out.push_str(&format!("motd_uptime_seconds {}\n", facts.uptime));
+
+    gauge(&mut out, "motd_processes", "Number of running processes");
+    out.push_str(&format!("motd_processes {}\n", facts.processes));
+
+    gauge(
+        &mut out,
+        "motd_tcp_connections",
+        "Number of TCP connections by state",
+    );
+    out.push_str(&format!(
+        "motd_tcp_connections{{state=\"listen\"}} {}\n",
+        facts.connections.listening
+    ));
+    out.push_str(&format!(
+        "motd_tcp_connections{{state=\"established\"}} {}\n",
+        facts.connections.established
+    ));
+
+    gauge(
+        &mut out,
+        "motd_logged_in_users",
+        "Number of distinct users logged in",
+    );
+    out.push_str(&format!("motd_logged_in_users {}\n", facts.users.len()));
+
+    gauge(
+        &mut out,
+        "motd_login_sessions",
+        "Number of open login sessions",
+    );
+    out.push_str(&format!("motd_login_sessions {}\n", facts.sessions.len()));
+
+    gauge(
+        &mut out,
+        "motd_interface_addresses",
+        "Number of addresses assigned to a network interface",
+    );
+    for interface in facts.interfaces.iter() {
+        out.push_str(&format!(
+            "motd_interface_addresses{{interface=\"{}\"}} {}\n",
+            escape_label(&interface.name),
+            interface.addrs.len()
+        ));
+    }
+
+    out
+}
+
+/// Writes facts to a Prometheus textfile (as read by node_exporter's
+/// textfile collector).  The metrics are written to a temporary file in
+/// the same directory and then renamed over `path`, so the collector never
+/// reads a partially written file
+///
+/// # Arguments
+///
+/// * `facts` - Facts to export
+/// * `path` - Path of the textfile to (re)place
+pub fn write_prometheus<P: AsRef<Path>>(facts: &Facts, path: P) -> MotdResult<()> {
+    let path = path.as_ref();
+
+    let mut tmp = PathBuf::from(path);
+    tmp.set_file_name(format!(
+        ".{}.{}",
+        path.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        process::id()
+    ));
+
+    let result = File::create(&tmp).and_then(|mut file| {
+        file.write_all(prometheus(facts).as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::motd::{
+        net::{Connections, Interface},
+        Motd,
+    };
+
+    fn facts() -> Facts {
+        let mut facts = Motd::new(Config::default()).facts();
+        facts.uptime = 3600;
+        facts.processes = 42;
+        facts.users = vec!["alice".to_string(), "bob".to_string()];
+        facts.sessions.clear();
+        facts.connections = Connections {
+            listening: 3,
+            established: 7,
+        };
+        facts.interfaces = vec![
+            Interface {
+                name: "eth0".to_string(),
+                addrs: vec!["10.0.0.2".to_string(), "10.0.0.3".to_string()],
+            },
+            Interface {
+                name: "we\"ird\\\n".to_string(),
+                addrs: vec![],
+            },
+        ];
+        facts
+    }
+
+    /// Returns the samples of the exposition, skipping HELP and TYPE lines
+    fn samples(text: &str) -> Vec<&str> {
+        text.lines().filter(|l| !l.starts_with('#')).collect()
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(escape_label("eth0"), "eth0");
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[test]
+    fn exports_every_metric() {
+        let text = prometheus(&facts());
+        assert_eq!(
+            samples(&text),
+            vec![
+                "motd_uptime_seconds 3600",
+                "motd_processes 42",
+                "motd_tcp_connections{state=\"listen\"} 3",
+                "motd_tcp_connections{state=\"established\"} 7",
+                "motd_logged_in_users 2",
+                "motd_login_sessions 0",
+                "motd_interface_addresses{interface=\"eth0\"} 2",
+                "motd_interface_addresses{interface=\"we\\\"ird\\\\\\n\"} 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn describes_every_metric_once() {
+        let text = prometheus(&facts());
+        let names: Vec<&str> = text
+            .lines()
+            .filter_map(|l| l.strip_prefix("# TYPE "))
+            .collect();
+        for name in names.iter() {
+            assert!(name.ends_with(" gauge"));
+            assert_eq!(
+                text.matches(&format!("# HELP {} ", &name[..name.len() - 6]))
+                    .count(),
+                1
+            );
+        }
+
+        // Every sample belongs to a described metric
+        for sample in samples(&text) {
+            let name = sample.split(['{', ' ']).next().unwrap();
+            assert!(names.contains(&format!("{} gauge", name).as_str()));
+        }
+    }
+
+    #[test]
+    fn replaces_textfile_atomically() {
+        let dir = std::env::temp_dir().join(format!("motd-export-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("motd.prom");
+        fs::write(&path, "stale").unwrap();
+
+        let facts = facts();
+        write_prometheus(&facts, &path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), prometheus(&facts));
+
+        // Only the textfile is left behind, not the temporary file
+        let entries: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+
+        assert!(write_prometheus(&facts, dir.join("missing").join("motd.prom")).is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
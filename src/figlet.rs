@@ -0,0 +1,625 @@
+//! Big text banners rendered with FIGlet fonts.
+//!
+//! ```text
+//! {{ figlet(hostname) }}
+//! {{ figlet(hostname, font="standard") }}
+//! {{ figlet(user, font=small) }}
+//! {{ figlet("Welcome", font=/usr/share/figlet/standard.flf) }}
+//! ```
+//!
+//! Fonts are standard `.flf` FIGfont files, found by path or by name in one
+//! of the font directories.  FIGlet's `standard` font and the `block` and
+//! `small` fonts are bundled, so banners work without any fonts installed.
+//! Characters are joined using the font's horizontal layout: full width,
+//! fitting (kerning) or smushing with any of the six controlled smushing
+//! rules.
+
+use crate::error::{Error, MotdResult, ParsingError};
+use log::error;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use unicode_width::UnicodeWidthStr;
+
+/// Directories searched, in order, for a font by name
+pub const FONT_DIRS: [&str; 4] = [
+    "fonts",
+    "/etc/motd-rs/fonts",
+    "/usr/share/motd-rs/fonts",
+    "/usr/share/figlet",
+];
+
+/// Font used if none is given
+pub const DEFAULT_FONT: &str = "standard";
+
+/// Fonts built into the binary
+const BUNDLED_FONTS: [(&str, &str); 3] = [
+    ("standard", include_str!("../fonts/standard.flf")),
+    ("block", include_str!("../fonts/block.flf")),
+    ("small", include_str!("../fonts/small.flf")),
+];
+
+/// Characters every font defines, in order, after its comments
+const REQUIRED_CHARS: std::ops::RangeInclusive<u32> = 32..=126;
+
+/// Characters fonts may define after the required ones (ÄÖÜäöüß)
+const GERMAN_CHARS: [u32; 7] = [196, 214, 220, 228, 246, 252, 223];
+
+/// Layout bits of the header's full layout field
+const SMUSH_EQUAL: u32 = 1;
+const SMUSH_UNDERSCORE: u32 = 2;
+const SMUSH_HIERARCHY: u32 = 4;
+const SMUSH_PAIR: u32 = 8;
+const SMUSH_BIG_X: u32 = 16;
+const SMUSH_HARDBLANK: u32 = 32;
+const LAYOUT_KERNING: u32 = 64;
+const LAYOUT_SMUSHING: u32 = 128;
+
+/// How characters are joined horizontally
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// Every character keeps its full width
+    FullWidth,
+
+    /// Characters are moved together until they touch
+    Fitting,
+
+    /// Characters are moved together until they overlap by one column,
+    /// where the overlapping characters are merged by the given rules.  No
+    /// rules means universal smushing, where the right character wins
+    Smushing(u32),
+}
+
+/// A FIGlet font
+#[derive(Clone, Debug)]
+pub struct Font {
+    /// Character drawn as a space that is never smushed
+    pub hardblank: char,
+
+    /// Number of lines in every character
+    pub height: usize,
+
+    /// How characters are joined horizontally
+    pub layout: Layout,
+
+    /// Lines of each character
+    pub chars: HashMap<char, Vec<String>>,
+}
+
+impl Font {
+    /// Parses the contents of a `.flf` file
+    ///
+    /// # Arguments
+    ///
+    /// * `src` - Contents of the font file
+    pub fn parse(src: &str) -> MotdResult<Font> {
+        let mut lines = src.lines();
+        let header = lines.next().ok_or(ParsingError::InvalidFont)?;
+        if !header.starts_with("flf2a") {
+            error!("font is missing the flf2a signature");
+            return Err(ParsingError::InvalidFont.into());
+        }
+
+        // flf2a$ height baseline max_length old_layout comment_lines
+        //     [print_direction full_layout codetag_count]
+        let hardblank = header[5..]
+            .chars()
+            .next()
+            .ok_or(ParsingError::InvalidFont)?;
+        let fields = header[5 + hardblank.len_utf8()..]
+            .split_whitespace()
+            .map(str::parse::<i64>)
+            .collect::<Result<Vec<i64>, _>>()?;
+
+        if fields.len() < 5 || fields[0] < 1 {
+            return Err(ParsingError::InvalidFont.into());
+        }
+
+        let height = fields[0] as usize;
+        let layout = match fields.get(6) {
+            Some(full) => Font::full_layout(*full as u32),
+            None => Font::old_layout(fields[3]),
+        };
+
+        let mut lines = lines.skip(fields[4] as usize);
+        let mut chars = HashMap::new();
+
+        let read_char = |lines: &mut dyn Iterator<Item = &str>| -> Option<Vec<String>> {
+            let glyph: Vec<String> = lines.take(height).map(strip_endmarks).collect();
+            if glyph.len() == height {
+                Some(glyph)
+            } else {
+                None
+            }
+        };
+
+        for code in REQUIRED_CHARS {
+            let glyph = read_char(&mut lines).ok_or(ParsingError::InvalidFont)?;
+            chars.insert(char::from_u32(code).unwrap_or(' '), glyph);
+        }
+
+        for code in GERMAN_CHARS.iter() {
+            match read_char(&mut lines) {
+                Some(glyph) => chars.insert(char::from_u32(*code).unwrap_or(' '), glyph),
+                None => break,
+            };
+        }
+
+        // Any remaining characters are preceded by a line with their code,
+        // in decimal, octal (0 prefix) or hexadecimal (0x prefix)
+        while let Some(tag) = lines.next() {
+            let code = match tag.split_whitespace().next().and_then(parse_code) {
+                Some(code) => code,
+                None => break,
+            };
+
+            match read_char(&mut lines) {
+                Some(glyph) => {
+                    if let Some(c) = char::from_u32(code) {
+                        chars.insert(c, glyph);
+                    }
+                }
+                None => break,
+            }
+        }
+
+        Ok(Font {
+            hardblank,
+            height,
+            layout,
+            chars,
+        })
+    }
+
+    /// Returns the layout described by the header's full layout field
+    fn full_layout(full: u32) -> Layout {
+        if full & LAYOUT_SMUSHING != 0 {
+            Layout::Smushing(full & 63)
+        } else if full & LAYOUT_KERNING != 0 {
+            Layout::Fitting
+        } else {
+            Layout::FullWidth
+        }
+    }
+
+    /// Returns the layout described by the header's old layout field, used
+    /// by fonts without a full layout
+    fn old_layout(old: i64) -> Layout {
+        match old {
+            o if o < 0 => Layout::FullWidth,
+            0 => Layout::Fitting,
+            o => Layout::Smushing(o as u32 & 63),
+        }
+    }
+
+    /// Loads a font from a `.flf` file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the font file
+    pub fn load<P: AsRef<Path>>(path: P) -> MotdResult<Font> {
+        Font::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Finds a font, either by path, by name in one of the font
+    /// directories, or by name among the bundled fonts
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of, or path to, the font
+    pub fn find(name: &str) -> MotdResult<Font> {
+        if Path::new(name).is_file() {
+            return Font::load(name);
+        }
+
+        let path = FONT_DIRS
+            .iter()
+            .map(|dir| PathBuf::from(dir).join(format!("{}.flf", name)))
+            .find(|path| path.is_file());
+
+        if let Some(path) = path {
+            return Font::load(path);
+        }
+
+        match BUNDLED_FONTS.iter().find(|(font, _)| *font == name) {
+            Some((_, src)) => Font::parse(src),
+            None => {
+                error!("font {} not found", name);
+                Err(Error::CommandFailed)
+            }
+        }
+    }
+
+    /// Renders text as a banner.  Characters the font doesn't define are
+    /// skipped, and each line of the text becomes its own banner
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Text to render
+    pub fn render(&self, text: &str) -> String {
+        let mut banners = Vec::new();
+
+        for line in text.lines() {
+            let mut rows = vec![String::new(); self.height];
+            let mut prev_width = 0;
+
+            for c in line.chars() {
+                let glyph = match self.chars.get(&c) {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+
+                let width = glyph.iter().map(|r| r.width()).max().unwrap_or(0);
+                self.append(&mut rows, glyph, prev_width, width);
+                prev_width = width;
+            }
+
+            let hardblank = self.hardblank.to_string();
+            let rows: Vec<String> = rows
+                .iter()
+                .map(|row| row.replace(&hardblank, " ").trim_end().to_string())
+                .collect();
+
+            banners.push(rows.join("\n"));
+        }
+
+        banners.join("\n")
+    }
+
+    /// Appends a character to the rows rendered so far, overlapping it
+    /// with them as much as the layout allows
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Rows rendered so far
+    /// * `glyph` - Lines of the character to add
+    /// * `prev_width` - Width of the previous character
+    /// * `width` - Width of the character to add
+    fn append(&self, rows: &mut [String], glyph: &[String], prev_width: usize, width: usize) {
+        let mut lines: Vec<Vec<char>> = rows.iter().map(|r| r.chars().collect()).collect();
+        let glyph: Vec<Vec<char>> = glyph.iter().map(|r| r.chars().collect()).collect();
+
+        let overlap = if self.layout == Layout::FullWidth {
+            0
+        } else {
+            lines
+                .iter()
+                .zip(glyph.iter())
+                .map(|(line, row)| self.row_overlap(line, row, prev_width, width))
+                .fold(width, usize::min)
+        };
+
+        for (line, row) in lines.iter_mut().zip(glyph.iter()) {
+            // Columns that would overlap past the start of the line are
+            // blank, since the overlap never exceeds the line's spacing
+            let skipped = overlap.saturating_sub(line.len());
+            let start = line.len() + skipped - overlap;
+
+            for (i, &rc) in row.iter().enumerate() {
+                if i < skipped {
+                    continue;
+                }
+
+                if i < overlap {
+                    let col = start + i - skipped;
+                    line[col] = match self.smush(line[col], rc, prev_width, width) {
+                        Some(c) => c,
+                        None => rc,
+                    };
+                } else {
+                    line.push(rc);
+                }
+            }
+        }
+
+        for (row, line) in rows.iter_mut().zip(lines) {
+            *row = line.into_iter().collect();
+        }
+    }
+
+    /// Returns how many columns a row of a character can overlap the end
+    /// of a rendered line: the blank space between them, plus one if the
+    /// characters that meet can be smushed
+    fn row_overlap(&self, line: &[char], row: &[char], prev_width: usize, width: usize) -> usize {
+        let trailing = line.iter().rev().take_while(|c| **c == ' ').count();
+        let leading = row.iter().take_while(|c| **c == ' ').count();
+        let lc = line.len().checked_sub(trailing + 1).map(|i| line[i]);
+        let rc = row.get(leading).copied();
+
+        match (lc, rc) {
+            (Some(lc), Some(rc)) if self.smush(lc, rc, prev_width, width).is_some() => {
+                trailing + leading + 1
+            }
+            _ => trailing + leading,
+        }
+    }
+
+    /// Returns the character two overlapping characters merge into, or
+    /// None if they can't be merged
+    fn smush(&self, lc: char, rc: char, prev_width: usize, width: usize) -> Option<char> {
+        if lc == ' ' {
+            return Some(rc);
+        }
+        if rc == ' ' {
+            return Some(lc);
+        }
+
+        let rules = match self.layout {
+            Layout::Smushing(rules) if prev_width >= 2 && width >= 2 => rules,
+            _ => return None,
+        };
+
+        let hb = self.hardblank;
+        if rules == 0 {
+            // Universal smushing: visible characters beat hardblanks, and
+            // otherwise the right character wins
+            return Some(if rc == hb { lc } else { rc });
+        }
+
+        if lc == hb || rc == hb {
+            return if lc == rc && rules & SMUSH_HARDBLANK != 0 {
+                Some(lc)
+            } else {
+                None
+            };
+        }
+
+        if rules & SMUSH_EQUAL != 0 && lc == rc {
+            return Some(lc);
+        }
+
+        if rules & SMUSH_UNDERSCORE != 0 {
+            const BORDERS: &str = "|/\\[]{}()<>";
+            if lc == '_' && BORDERS.contains(rc) {
+                return Some(rc);
+            }
+            if rc == '_' && BORDERS.contains(lc) {
+                return Some(lc);
+            }
+        }
+
+        if rules & SMUSH_HIERARCHY != 0 {
+            const CLASSES: [&str; 6] = ["|", "/\\", "[]", "{}", "()", "<>"];
+            let class = |c: char| CLASSES.iter().position(|class| class.contains(c));
+            match (class(lc), class(rc)) {
+                (Some(l), Some(r)) if l < r => return Some(rc),
+                (Some(l), Some(r)) if l > r => return Some(lc),
+                _ => {}
+            }
+        }
+
+        if rules & SMUSH_PAIR != 0 {
+            match (lc, rc) {
+                ('[', ']') | (']', '[') | ('{', '}') | ('}', '{') | ('(', ')') | (')', '(') => {
+                    return Some('|')
+                }
+                _ => {}
+            }
+        }
+
+        if rules & SMUSH_BIG_X != 0 {
+            match (lc, rc) {
+                ('/', '\\') => return Some('|'),
+                ('\\', '/') => return Some('Y'),
+                ('>', '<') => return Some('X'),
+                _ => {}
+            }
+        }
+
+        None
+    }
+}
+
+/// Removes the end marks (usually `@`) from a line of a character.  The
+/// last character of the line is the end mark, and it may be repeated
+fn strip_endmarks(line: &str) -> String {
+    let line = line.trim_end_matches(['\r', '\n'].as_ref());
+    match line.chars().last() {
+        Some(mark) => line.trim_end_matches(mark).to_string(),
+        None => String::new(),
+    }
+}
+
+/// Parses the code of a code tagged character
+fn parse_code(code: &str) -> Option<u32> {
+    let (digits, radix) = if let Some(hex) = code.strip_prefix("0x").or(code.strip_prefix("0X")) {
+        (hex, 16)
+    } else if code.len() > 1 && code.starts_with('0') {
+        (&code[1..], 8)
+    } else {
+        (code, 10)
+    };
+
+    // Negative codes are reserved for translation tables
+    u32::from_str_radix(digits, radix).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a font with two line characters, each drawn as the character
+    /// twice, followed by any extra lines
+    fn font_src(header: &str, extra: &str) -> String {
+        let mut src = format!("{}\ncomment\n", header);
+        for code in REQUIRED_CHARS {
+            let c = char::from_u32(code).unwrap();
+            let c = if c == '@' { '#' } else { c };
+            src.push_str(&format!("{}{}@\n{}{}@@\n", c, c, c, c));
+        }
+        src.push_str(extra);
+        src
+    }
+
+    fn font(layout: Layout) -> Font {
+        Font {
+            hardblank: '$',
+            height: 1,
+            layout,
+            chars: HashMap::new(),
+        }
+    }
+
+    fn smush(rules: u32, lc: char, rc: char) -> Option<char> {
+        font(Layout::Smushing(rules)).smush(lc, rc, 2, 2)
+    }
+
+    #[test]
+    fn parses_header_and_characters() {
+        let font = Font::parse(&font_src("flf2a$ 2 1 4 -1 1", "")).unwrap();
+        assert_eq!(font.hardblank, '$');
+        assert_eq!(font.height, 2);
+        assert_eq!(font.layout, Layout::FullWidth);
+        assert_eq!(font.chars.len(), 95);
+        assert_eq!(font.chars[&'A'], vec!["AA", "AA"]);
+        assert_eq!(font.chars[&' '], vec!["  ", "  "]);
+    }
+
+    #[test]
+    fn parses_layouts() {
+        let layout = |header: &str| Font::parse(&font_src(header, "")).unwrap().layout;
+        assert_eq!(layout("flf2a$ 2 1 4 0 1"), Layout::Fitting);
+        assert_eq!(layout("flf2a$ 2 1 4 15 1"), Layout::Smushing(15));
+        assert_eq!(layout("flf2a$ 2 1 4 -1 1 0 64"), Layout::Fitting);
+        assert_eq!(layout("flf2a$ 2 1 4 -1 1 0 24463"), Layout::Smushing(15));
+        assert_eq!(layout("flf2a$ 2 1 4 15 1 0 0"), Layout::FullWidth);
+    }
+
+    #[test]
+    fn parses_german_and_code_tagged_characters() {
+        let mut extra = String::new();
+        for c in "ÄÖÜäöüß".chars() {
+            extra.push_str(&format!("{}@\n{}@@\n", c, c));
+        }
+        extra.push_str("0x263A SMILE\n:)@\n:)@@\n");
+        extra.push_str("0100\n@@@\n@@@@\n");
+
+        let font = Font::parse(&font_src("flf2a$ 2 1 4 -1 1", &extra)).unwrap();
+        assert_eq!(font.chars[&'ß'], vec!["ß", "ß"]);
+        assert_eq!(font.chars[&'☺'], vec![":)", ":)"]);
+        assert_eq!(font.chars[&'@'], vec!["", ""]);
+    }
+
+    #[test]
+    fn rejects_invalid_fonts() {
+        assert!(Font::parse("").is_err());
+        assert!(Font::parse(&font_src("flf2b$ 2 1 4 -1 1", "")).is_err());
+        assert!(Font::parse(&font_src("flf2a$ 0 1 4 -1 1", "")).is_err());
+        assert!(Font::parse(&font_src("flf2a$ 2 1 4", "")).is_err());
+
+        let mut truncated = font_src("flf2a$ 2 1 4 -1 1", "");
+        truncated.truncate(truncated.len() - 5);
+        assert!(Font::parse(&truncated).is_err());
+    }
+
+    #[test]
+    fn strips_endmarks() {
+        assert_eq!(strip_endmarks(" _ @"), " _ ");
+        assert_eq!(strip_endmarks(" _ @@"), " _ ");
+        assert_eq!(strip_endmarks(" _ #\r"), " _ ");
+        assert_eq!(strip_endmarks(""), "");
+    }
+
+    #[test]
+    fn parses_codes() {
+        assert_eq!(parse_code("196"), Some(196));
+        assert_eq!(parse_code("0304"), Some(196));
+        assert_eq!(parse_code("0xC4"), Some(196));
+        assert_eq!(parse_code("-1"), None);
+    }
+
+    #[test]
+    fn smushes_equal_characters() {
+        assert_eq!(smush(SMUSH_EQUAL, '|', '|'), Some('|'));
+        assert_eq!(smush(SMUSH_EQUAL, '|', '/'), None);
+        assert_eq!(smush(SMUSH_UNDERSCORE, '|', '|'), None);
+    }
+
+    #[test]
+    fn smushes_underscores() {
+        assert_eq!(smush(SMUSH_UNDERSCORE, '_', '/'), Some('/'));
+        assert_eq!(smush(SMUSH_UNDERSCORE, '[', '_'), Some('['));
+        assert_eq!(smush(SMUSH_UNDERSCORE, '_', 'a'), None);
+    }
+
+    #[test]
+    fn smushes_by_hierarchy() {
+        assert_eq!(smush(SMUSH_HIERARCHY, '|', '/'), Some('/'));
+        assert_eq!(smush(SMUSH_HIERARCHY, '}', '('), Some('('));
+        assert_eq!(smush(SMUSH_HIERARCHY, '<', '['), Some('<'));
+        assert_eq!(smush(SMUSH_HIERARCHY, '/', '\\'), None);
+    }
+
+    #[test]
+    fn smushes_opposite_pairs() {
+        assert_eq!(smush(SMUSH_PAIR, '[', ']'), Some('|'));
+        assert_eq!(smush(SMUSH_PAIR, '}', '{'), Some('|'));
+        assert_eq!(smush(SMUSH_PAIR, ')', '('), Some('|'));
+        assert_eq!(smush(SMUSH_PAIR, '<', '>'), None);
+    }
+
+    #[test]
+    fn smushes_big_x() {
+        assert_eq!(smush(SMUSH_BIG_X, '/', '\\'), Some('|'));
+        assert_eq!(smush(SMUSH_BIG_X, '\\', '/'), Some('Y'));
+        assert_eq!(smush(SMUSH_BIG_X, '>', '<'), Some('X'));
+        assert_eq!(smush(SMUSH_BIG_X, '<', '>'), None);
+    }
+
+    #[test]
+    fn smushes_hardblanks() {
+        assert_eq!(smush(SMUSH_HARDBLANK, '$', '$'), Some('$'));
+        assert_eq!(smush(SMUSH_EQUAL, '$', '$'), None);
+        assert_eq!(smush(63, '$', '|'), None);
+    }
+
+    #[test]
+    fn smushes_universally() {
+        assert_eq!(smush(0, 'a', 'b'), Some('b'));
+        assert_eq!(smush(0, 'a', '$'), Some('a'));
+        assert_eq!(smush(0, '$', 'b'), Some('b'));
+    }
+
+    #[test]
+    fn only_smushes_blanks_otherwise() {
+        assert_eq!(smush(63, ' ', '|'), Some('|'));
+        assert_eq!(smush(63, '|', ' '), Some('|'));
+        assert_eq!(font(Layout::Smushing(63)).smush('|', '|', 1, 2), None);
+        assert_eq!(font(Layout::Fitting).smush('|', '|', 2, 2), None);
+        assert_eq!(font(Layout::FullWidth).smush('|', '|', 2, 2), None);
+    }
+
+    #[test]
+    fn renders_layouts() {
+        let mut font = font(Layout::FullWidth);
+        font.chars.insert('a', vec!["|  ".to_string()]);
+        font.chars.insert('b', vec![" |  ".to_string()]);
+        assert_eq!(font.render("ab"), "|   |");
+
+        font.layout = Layout::Fitting;
+        assert_eq!(font.render("ab"), "||");
+
+        font.layout = Layout::Smushing(SMUSH_EQUAL);
+        assert_eq!(font.render("ab"), "|");
+    }
+
+    #[test]
+    fn renders_standard_font() {
+        let font = Font::parse(BUNDLED_FONTS[0].1).unwrap();
+        assert_eq!(font.layout, Layout::Smushing(15));
+        assert_eq!(
+            font.render("Hello"),
+            [
+                " _   _      _ _",
+                "| | | | ___| | | ___",
+                "| |_| |/ _ \\ | |/ _ \\",
+                "|  _  |  __/ | | (_) |",
+                "|_| |_|\\___|_|_|\\___/",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -0,0 +1,547 @@
+//! Fortune database reader, compatible with the files used by `fortune`.
+//!
+//! Fortunes are read from every file in the configured directories.  Files
+//! hold fortunes separated by lines containing just `%`, and may have a
+//! `.dat` index built by `strfile`, which is used to find fortunes without
+//! scanning the file.  Offensive fortunes are those in an `off` directory
+//! or in files whose names end in `-o`, and are often ROT13 encoded.
+//!
+//! ```text
+//! {{ fortune }}
+//! {{ fortune(short, daily) }}
+//! {{ fortune(offensive=exclude, max_length=120) }}
+//! ```
+//!
+//! The classic `fortune` flags `-a` (all), `-o` (offensive only) and `-s`
+//! (short) are accepted too.  Every fortune is equally likely to be chosen,
+//! no matter which file it's in.
+
+use crate::{
+    ansi,
+    config::FortuneConfig,
+    error::{MotdResult, ParsingError},
+    template,
+};
+use chrono::{offset::Local, Datelike};
+use serde::Deserialize;
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Shown when no fortune could be found
+pub const NO_FORTUNE: &str = "No Fortune";
+
+/// Longest fortune considered short, as in `fortune -s`
+pub const SHORT_LENGTH: usize = 160;
+
+/// Flag in a `.dat` header marking an index whose fortunes are shuffled
+/// (`strfile -r`)
+const STR_RANDOM: u32 = 0x1;
+
+/// Flag in a `.dat` header marking an index whose fortunes are sorted by
+/// text (`strfile -o`)
+const STR_ORDERED: u32 = 0x2;
+
+/// Flag in a `.dat` header marking a ROT13 encoded file
+const STR_ROTATED: u32 = 0x4;
+
+/// Size of a `.dat` header: version, count, longest, shortest and flags,
+/// followed by the delimiter and three bytes of padding
+const DAT_HEADER_LEN: usize = 24;
+
+/// Which fortunes to choose from, by offensiveness
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Offensive {
+    /// Only fortunes that aren't offensive
+    Exclude,
+
+    /// Every fortune
+    Include,
+
+    /// Only offensive fortunes
+    Only,
+}
+
+/// A file of fortunes, along with where each one starts and ends
+#[derive(Clone, Debug)]
+struct Source {
+    /// Path to the fortune file
+    path: PathBuf,
+
+    /// Fortunes are ROT13 encoded
+    rotated: bool,
+
+    /// Byte range of each fortune in the file
+    ranges: Vec<(u64, u64)>,
+}
+
+/// Options for choosing a fortune
+#[derive(Clone, Debug)]
+pub struct Fortunes {
+    /// Directories (or individual files) to read fortunes from
+    pub dirs: Vec<PathBuf>,
+
+    /// Which fortunes to choose from, by offensiveness
+    pub offensive: Offensive,
+
+    /// Only choose fortunes no longer than this many bytes
+    pub max_length: Option<usize>,
+
+    /// Choose the same fortune all day
+    pub daily: bool,
+}
+
+impl Fortunes {
+    /// Creates options from the configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Fortune configuration
+    pub fn new(config: &FortuneConfig) -> Fortunes {
+        Fortunes {
+            dirs: config.dirs.clone(),
+            offensive: config.offensive,
+            max_length: config.max_length,
+            daily: config.daily,
+        }
+    }
+
+    /// Overrides options with the arguments of a `fortune` command, which
+    /// are separated by commas or spaces outside of quotes
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments passed to the command
+    pub fn args(mut self, args: &str) -> Self {
+        let args = template::split_top_level(args, ',')
+            .into_iter()
+            .flat_map(|arg| template::split_top_level(arg, ' '));
+
+        for arg in args {
+            let arg = arg.trim();
+            let (key, value) = match arg.split_once('=') {
+                Some((key, value)) => (key.trim(), ansi::unquote(value.trim())),
+                None => (arg, ""),
+            };
+
+            match key {
+                "" => {}
+                "-a" | "all" => self.offensive = Offensive::Include,
+                "-o" => self.offensive = Offensive::Only,
+                "offensive" => {
+                    self.offensive = match value {
+                        "exclude" | "no" => Offensive::Exclude,
+                        "only" => Offensive::Only,
+                        _ => Offensive::Include,
+                    }
+                }
+                "-s" | "short" => self.max_length = Some(SHORT_LENGTH),
+                "max_length" => self.max_length = value.parse().ok(),
+                "daily" => self.daily = true,
+                "dir" => self.dirs = vec![PathBuf::from(value)],
+                key => log::error!("unknown fortune argument {}", key),
+            }
+        }
+
+        self
+    }
+
+    /// Chooses a fortune, or returns None if there are no fortunes to
+    /// choose from
+    pub fn choose(&self) -> Option<String> {
+        let sources: Vec<Source> = self
+            .files()
+            .iter()
+            .filter_map(|(path, offensive)| match self.offensive {
+                Offensive::Exclude if *offensive => None,
+                Offensive::Only if !*offensive => None,
+                _ => Source::load(path).ok(),
+            })
+            .collect();
+
+        // Ranges include the delimiter line (`%` and a newline)
+        let fits = |(start, end): &(u64, u64)| match self.max_length {
+            Some(max) => (end - start).saturating_sub(2) as usize <= max,
+            None => true,
+        };
+
+        let total: usize = sources
+            .iter()
+            .map(|s| s.ranges.iter().filter(|r| fits(r)).count())
+            .sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut n = (self.seed() % total as u64) as usize;
+        for source in sources.iter() {
+            for range in source.ranges.iter().filter(|r| fits(r)) {
+                if n == 0 {
+                    return source.read(*range).ok();
+                }
+                n -= 1;
+            }
+        }
+
+        None
+    }
+
+    /// Returns every fortune file in the directories, and whether it holds
+    /// offensive fortunes, sorted so choices are stable
+    fn files(&self) -> Vec<(PathBuf, bool)> {
+        let mut files = Vec::new();
+
+        for dir in self.dirs.iter() {
+            if dir.is_file() {
+                files.push((dir.clone(), is_offensive(dir)));
+                continue;
+            }
+
+            for (path, in_off) in list_files(dir, false)
+                .into_iter()
+                .chain(list_files(&dir.join("off"), true))
+            {
+                let offensive = in_off || is_offensive(&path);
+                files.push((path, offensive));
+            }
+        }
+
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    /// Returns the number used to choose a fortune: the same all day if
+    /// `daily` is set, otherwise different every time.  The daily seed is
+    /// hashed with FNV-1a so it doesn't change between builds
+    fn seed(&self) -> u64 {
+        if self.daily {
+            let day = Local::now().num_days_from_ce();
+            fnv1a(&day.to_le_bytes())
+        } else {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or(0);
+            fnv1a(&[now.to_le_bytes(), (process::id() as u128).to_le_bytes()].concat())
+        }
+    }
+}
+
+impl Source {
+    /// Loads the ranges of the fortunes in a file, from its `.dat` index if
+    /// it has one, otherwise by scanning the file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the fortune file
+    fn load(path: &Path) -> MotdResult<Source> {
+        let mut dat = path.as_os_str().to_owned();
+        dat.push(".dat");
+
+        match fs::read(&dat) {
+            Ok(index) => Source::from_index(path, &index),
+            Err(_) => Source::scan(path),
+        }
+    }
+
+    /// Reads the ranges of the fortunes in a file from a `.dat` index
+    /// built by `strfile`.  All numbers are big-endian
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the fortune file
+    /// * `index` - Contents of the index
+    fn from_index(path: &Path, index: &[u8]) -> MotdResult<Source> {
+        if index.len() < DAT_HEADER_LEN {
+            return Err(ParsingError::NumberConversionFailed.into());
+        }
+
+        let word = |i: usize| -> u32 {
+            let b = &index[i * 4..i * 4 + 4];
+            u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+        };
+
+        let count = word(1) as usize;
+        let flags = word(4);
+
+        // The count is followed by one more offset, the end of the file
+        let mut offsets: Vec<u64> = (0..=count)
+            .map(|i| DAT_HEADER_LEN / 4 + i)
+            .take_while(|i| (i + 1) * 4 <= index.len())
+            .map(|i| word(i) as u64)
+            .collect();
+
+        // Indexes built with `strfile -r` or `-o` list the fortunes shuffled
+        // or sorted by text.  Every fortune is equally likely to be chosen
+        // either way, so just put the offsets back in file order
+        if flags & (STR_RANDOM | STR_ORDERED) != 0 {
+            offsets.sort_unstable();
+        }
+
+        let ranges = offsets
+            .windows(2)
+            .map(|w| (w[0], w[1]))
+            .filter(|(start, end)| end > start)
+            .collect();
+
+        Ok(Source {
+            path: path.to_path_buf(),
+            rotated: flags & STR_ROTATED != 0,
+            ranges,
+        })
+    }
+
+    /// Finds the fortunes in a file without an index by looking for the
+    /// `%` lines between them
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the fortune file
+    fn scan(path: &Path) -> MotdResult<Source> {
+        let contents = fs::read(path)?;
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        let mut pos = 0;
+
+        for line in contents.split_inclusive(|b| *b == b'\n') {
+            let end = pos + line.len();
+            if line == b"%\n" || line == b"%" || line == b"%\r\n" {
+                ranges.push((start as u64, end as u64));
+                start = end;
+            }
+            pos = end;
+        }
+
+        ranges.push((start as u64, pos as u64));
+
+        Ok(Source {
+            path: path.to_path_buf(),
+            rotated: false,
+            ranges: ranges
+                .into_iter()
+                .filter(|(s, e)| {
+                    let text = &contents[*s as usize..*e as usize];
+                    !trim_delimiter(&String::from_utf8_lossy(text))
+                        .trim()
+                        .is_empty()
+                })
+                .collect(),
+        })
+    }
+
+    /// Reads a single fortune from the file
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - Byte range of the fortune
+    fn read(&self, (start, end): (u64, u64)) -> MotdResult<String> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(start))?;
+
+        let mut contents = Vec::new();
+        file.take(end.saturating_sub(start))
+            .read_to_end(&mut contents)?;
+        let text = String::from_utf8_lossy(&contents);
+        let text = trim_delimiter(&text).trim_end();
+
+        Ok(if self.rotated {
+            rot13(text)
+        } else {
+            text.to_string()
+        })
+    }
+}
+
+/// Returns the fortune files in a directory.  Indexes and other files that
+/// `fortune` ignores are skipped
+///
+/// # Arguments
+///
+/// * `dir` - Directory to list
+/// * `offensive` - Value to pair each file with
+fn list_files(dir: &Path, offensive: bool) -> Vec<(PathBuf, bool)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy())
+                .unwrap_or_default();
+            let ext = path
+                .extension()
+                .map(|e| e.to_string_lossy())
+                .unwrap_or_default();
+            !name.starts_with('.') && !matches!(ext.as_ref(), "dat" | "u8" | "pdat" | "sh")
+        })
+        .map(|path| (path, offensive))
+        .collect()
+}
+
+/// Returns true if a file's name marks it as offensive (e.g., `art-o`)
+fn is_offensive(path: &Path) -> bool {
+    path.file_name()
+        .map(|n| n.to_string_lossy().ends_with("-o"))
+        .unwrap_or(false)
+}
+
+/// Removes the `%` line that ends a fortune
+fn trim_delimiter(text: &str) -> &str {
+    let trimmed = text.trim_end_matches(['\n', '\r']);
+    match trimmed.strip_suffix('%') {
+        Some(rest) if rest.is_empty() || rest.ends_with('\n') => rest,
+        _ => text,
+    }
+}
+
+/// Hashes bytes with 64-bit FNV-1a
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Decodes (or encodes) ROT13 text
+fn rot13(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'a'..='z' => (((c as u8 - b'a') + 13) % 26 + b'a') as char,
+            'A'..='Z' => (((c as u8 - b'A') + 13) % 26 + b'A') as char,
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a fortune file to a temporary path unique to the test
+    fn write_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("motd-fortune-{}-{}", process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Builds a `.dat` index with the given flags and offsets
+    fn index(flags: u32, offsets: &[u32]) -> Vec<u8> {
+        let count = offsets.len() as u32 - 1;
+        let mut data = Vec::new();
+        for word in [2, count, 100, 1, flags].iter() {
+            data.extend_from_slice(&word.to_be_bytes());
+        }
+        data.extend_from_slice(b"%\0\0\0");
+        for offset in offsets {
+            data.extend_from_slice(&offset.to_be_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn reads_index() {
+        let path = Path::new("fortunes");
+        let source = Source::from_index(path, &index(0, &[0, 10, 25, 25, 40])).unwrap();
+        assert!(!source.rotated);
+        assert_eq!(source.ranges, vec![(0, 10), (10, 25), (25, 40)]);
+
+        let source = Source::from_index(path, &index(STR_ROTATED, &[0, 10])).unwrap();
+        assert!(source.rotated);
+        assert_eq!(source.ranges, vec![(0, 10)]);
+    }
+
+    #[test]
+    fn reads_reordered_index() {
+        let path = Path::new("fortunes");
+        for flags in [STR_RANDOM, STR_ORDERED].iter() {
+            let source = Source::from_index(path, &index(*flags, &[25, 0, 10, 40])).unwrap();
+            assert_eq!(source.ranges, vec![(0, 10), (10, 25), (25, 40)]);
+        }
+    }
+
+    #[test]
+    fn hashes_seeds_stably() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+
+        let config = FortuneConfig::default();
+        let daily = Fortunes::new(&config).args("daily");
+        assert_eq!(daily.seed(), daily.seed());
+    }
+
+    #[test]
+    fn reads_truncated_index() {
+        let path = Path::new("fortunes");
+        let mut data = index(0, &[0, 10, 25]);
+        data.truncate(data.len() - 2);
+        let source = Source::from_index(path, &data).unwrap();
+        assert_eq!(source.ranges, vec![(0, 10)]);
+
+        assert!(Source::from_index(path, &data[..DAT_HEADER_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn scans_and_reads_fortunes() {
+        let path = write_file("scan", "one\n%\n\n%\ntwo\nlines\n%\r\nthree");
+        let source = Source::scan(&path).unwrap();
+        assert_eq!(source.ranges, vec![(0, 6), (9, 22), (22, 27)]);
+
+        let fortunes: Vec<String> = source
+            .ranges
+            .iter()
+            .map(|range| source.read(*range).unwrap())
+            .collect();
+        assert_eq!(fortunes, vec!["one", "two\nlines", "three"]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reads_rotated_fortunes() {
+        let path = write_file("rot", "Uryyb\n%\n");
+        let source = Source {
+            path: path.clone(),
+            rotated: true,
+            ranges: vec![(0, 8)],
+        };
+        assert_eq!(source.read((0, 8)).unwrap(), "Hello");
+        assert_eq!(source.read((0, 100)).unwrap(), "Hello");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn trims_delimiters() {
+        assert_eq!(trim_delimiter("one\n%\n"), "one\n");
+        assert_eq!(trim_delimiter("one\n%\r\n"), "one\n");
+        assert_eq!(trim_delimiter("%\n"), "");
+        assert_eq!(trim_delimiter("100%\n"), "100%\n");
+        assert_eq!(trim_delimiter("one\n"), "one\n");
+    }
+
+    #[test]
+    fn parses_args() {
+        let config = FortuneConfig::default();
+        let fortunes = Fortunes::new(&config).args(r#"short, daily, dir="/a, b""#);
+        assert_eq!(fortunes.max_length, Some(SHORT_LENGTH));
+        assert!(fortunes.daily);
+        assert_eq!(fortunes.dirs, vec![PathBuf::from("/a, b")]);
+
+        let fortunes = Fortunes::new(&config).args("-o -s");
+        assert_eq!(fortunes.offensive, Offensive::Only);
+        assert_eq!(fortunes.max_length, Some(SHORT_LENGTH));
+
+        let fortunes = Fortunes::new(&config).args("offensive=exclude, max_length=80");
+        assert_eq!(fortunes.offensive, Offensive::Exclude);
+        assert_eq!(fortunes.max_length, Some(80));
+    }
+}
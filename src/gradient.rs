@@ -0,0 +1,280 @@
+//! Color gradients applied per character cell to blocks of ASCII art.
+//!
+//! ```text
+//! {% gradient from=art to=accent direction=diagonal %}
+//!   /\_/\     Hostname: {{ hostname }}
+//!  ( o.o )
+//! {% endgradient %}
+//! ```
+//!
+//! Only the literal text of the block (the art) is colored.  The output of
+//! expressions and nested blocks, such as the `{{ hostname }}` above, is left
+//! exactly as rendered, though it still takes up space in the gradient.
+
+use crate::{
+    ansi::{self, Color, Style, RESET},
+    theme::Theme,
+};
+use std::f64::consts::PI;
+use unicode_width::UnicodeWidthChar;
+
+/// Which way the colors of a gradient change
+#[derive(Clone, Copy, Debug)]
+pub enum Direction {
+    /// Left to right
+    Horizontal,
+
+    /// Top to bottom
+    Vertical,
+
+    /// Top left to bottom right
+    Diagonal,
+}
+
+/// The colors a gradient moves through
+#[derive(Clone, Copy, Debug)]
+pub enum Fill {
+    /// Blends from the first color to the second
+    Stops(Color, Color),
+
+    /// Cycles through the rainbow (like lolcat), changing hue by `freq`
+    /// radians per character cell
+    Rainbow { freq: f64 },
+}
+
+/// A gradient to apply to a block of art
+#[derive(Clone, Copy, Debug)]
+pub struct Gradient {
+    pub direction: Direction,
+    pub fill: Fill,
+}
+
+impl Gradient {
+    /// Parses the arguments of a gradient block, such as
+    /// `from=#ff0000 to=#0000ff direction=vertical` or `rainbow freq=0.3`.
+    /// Colors may be palette or role names from the theme.  Returns None if
+    /// neither `rainbow` nor both colors are given
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Whitespace separated arguments of the block
+    /// * `theme` - Theme to resolve color names with
+    pub fn parse(args: &str, theme: &Theme) -> Option<Gradient> {
+        let mut direction = Direction::Horizontal;
+        let mut rainbow = false;
+        let mut freq = 0.1;
+        let mut from = None;
+        let mut to = None;
+
+        for arg in args.split_whitespace() {
+            let (key, value) = match arg.find('=') {
+                Some(i) => (&arg[..i], ansi::unquote(&arg[i + 1..])),
+                None => (arg, ""),
+            };
+
+            match key {
+                "rainbow" => rainbow = true,
+                "freq" => freq = value.parse().unwrap_or(freq),
+                "from" => from = theme.color(value),
+                "to" => to = theme.color(value),
+                "direction" => {
+                    direction = match value {
+                        "vertical" => Direction::Vertical,
+                        "diagonal" => Direction::Diagonal,
+                        _ => Direction::Horizontal,
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let fill = match (rainbow, from, to) {
+            (true, _, _) => Fill::Rainbow { freq },
+            (false, Some(from), Some(to)) => Fill::Stops(from, to),
+            _ => return None,
+        };
+
+        Some(Gradient { direction, fill })
+    }
+
+    /// Returns the color of the character cell at `row` and `col`
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - Line of the cell
+    /// * `col` - Column of the cell
+    /// * `rows` - Number of lines in the block
+    /// * `cols` - Number of columns in the widest line of the block
+    fn color_at(&self, row: usize, col: usize, rows: usize, cols: usize) -> Color {
+        let (pos, extent) = match self.direction {
+            Direction::Horizontal => (col, cols),
+            Direction::Vertical => (row, rows),
+            Direction::Diagonal => (row + col, rows + cols - 1),
+        };
+
+        match self.fill {
+            Fill::Stops(from, to) => {
+                let t = pos as f64 / (extent.max(2) - 1) as f64;
+                let (r1, g1, b1) = from.to_rgb();
+                let (r2, g2, b2) = to.to_rgb();
+                let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+
+                Color::Rgb(mix(r1, r2), mix(g1, g2), mix(b1, b2))
+            }
+            Fill::Rainbow { freq } => {
+                let i = freq * pos as f64;
+                let channel = |phase: f64| ((i + phase).sin() * 127.0 + 128.0) as u8;
+
+                Color::Rgb(
+                    channel(0.0),
+                    channel(2.0 * PI / 3.0),
+                    channel(4.0 * PI / 3.0),
+                )
+            }
+        }
+    }
+
+    /// Applies the gradient to a rendered block.  The block is made up of
+    /// parts that are either art (colored) or already rendered output (left
+    /// as-is), in the order they appear
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - Pieces of the block, each flagged true if it is art
+    pub fn apply(&self, parts: &[(bool, String)]) -> String {
+        let whole: String = parts.iter().map(|(_, s)| s.as_str()).collect();
+        let rows = whole.split('\n').count();
+        let cols = whole.split('\n').map(ansi::width).max().unwrap_or(0);
+
+        let mut s = String::new();
+        let (mut row, mut col) = (0, 0);
+
+        for (art, part) in parts {
+            if !art {
+                s.push_str(part);
+                match part.rfind('\n') {
+                    Some(i) => {
+                        row += part.matches('\n').count();
+                        col = ansi::width(&part[i + 1..]);
+                    }
+                    None => col += ansi::width(part),
+                }
+                continue;
+            }
+
+            let mut current: Option<Color> = None;
+            for c in part.chars() {
+                match c {
+                    '\n' => {
+                        if current.take().is_some() {
+                            s.push_str(RESET);
+                        }
+                        row += 1;
+                        col = 0;
+                    }
+                    c if c.is_whitespace() => col += 1,
+                    c => {
+                        let color = self.color_at(row, col, rows, cols);
+                        if current != Some(color) {
+                            let style = Style {
+                                fg: Some(color),
+                                ..Default::default()
+                            };
+                            s.push_str(&style.sgr());
+                            current = Some(color);
+                        }
+                        col += c.width().unwrap_or(0);
+                    }
+                }
+
+                s.push(c);
+            }
+
+            if current.is_some() {
+                s.push_str(RESET);
+            }
+        }
+
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stops(direction: Direction) -> Gradient {
+        Gradient {
+            direction,
+            fill: Fill::Stops(Color::Rgb(0, 0, 0), Color::Rgb(200, 100, 0)),
+        }
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let theme: Theme = toml::from_str("[palette]\nsky = \"#0000ff\"").unwrap();
+        let gradient = Gradient::parse("from=red to='sky' direction=vertical", &theme).unwrap();
+        assert!(matches!(gradient.direction, Direction::Vertical));
+        assert!(matches!(
+            gradient.fill,
+            Fill::Stops(Color::Indexed(1), Color::Rgb(0, 0, 255))
+        ));
+
+        let gradient = Gradient::parse("rainbow freq=0.3 direction=diagonal", &theme).unwrap();
+        assert!(matches!(gradient.direction, Direction::Diagonal));
+        assert!(matches!(gradient.fill, Fill::Rainbow { freq } if freq == 0.3));
+
+        assert!(Gradient::parse("from=red", &theme).is_none());
+        assert!(Gradient::parse("from=red to=nope", &theme).is_none());
+    }
+
+    #[test]
+    fn colors_each_cell() {
+        let gradient = stops(Direction::Horizontal);
+        assert_eq!(gradient.color_at(0, 0, 2, 5), Color::Rgb(0, 0, 0));
+        assert_eq!(gradient.color_at(1, 2, 2, 5), Color::Rgb(100, 50, 0));
+        assert_eq!(gradient.color_at(0, 4, 2, 5), Color::Rgb(200, 100, 0));
+
+        let gradient = stops(Direction::Vertical);
+        assert_eq!(gradient.color_at(1, 0, 3, 5), Color::Rgb(100, 50, 0));
+        assert_eq!(gradient.color_at(0, 0, 1, 1), Color::Rgb(0, 0, 0));
+
+        let gradient = stops(Direction::Diagonal);
+        assert_eq!(gradient.color_at(1, 1, 2, 3), Color::Rgb(133, 67, 0));
+
+        let rainbow = Gradient {
+            direction: Direction::Horizontal,
+            fill: Fill::Rainbow { freq: 0.0 },
+        };
+        assert_eq!(rainbow.color_at(0, 9, 1, 10), Color::Rgb(128, 237, 18));
+    }
+
+    #[test]
+    fn applies_to_art_only() {
+        let parts = vec![
+            (true, "ab c\n".to_string()),
+            (false, "xy".to_string()),
+            (true, "d".to_string()),
+        ];
+        let color = |r, g| {
+            Style {
+                fg: Some(Color::Rgb(r, g, 0)),
+                ..Default::default()
+            }
+            .sgr()
+        };
+
+        assert_eq!(
+            stops(Direction::Horizontal).apply(&parts),
+            format!(
+                "{}a{}b {}c{}\nxy{}d{}",
+                color(0, 0),
+                color(67, 33),
+                color(200, 100),
+                RESET,
+                color(133, 67),
+                RESET
+            )
+        );
+    }
+}
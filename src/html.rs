@@ -0,0 +1,134 @@
+//! Converts a rendered motd, including any ANSI styling, into HTML
+
+use crate::ansi::{self, Style};
+
+/// Escapes characters that have a special meaning in HTML
+///
+/// # Arguments
+///
+/// * `text` - Text to escape
+pub fn escape(text: &str) -> String {
+    let mut s = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => s.push_str("&amp;"),
+            '<' => s.push_str("&lt;"),
+            '>' => s.push_str("&gt;"),
+            '"' => s.push_str("&quot;"),
+            '\'' => s.push_str("&#39;"),
+            c => s.push(c),
+        }
+    }
+
+    s
+}
+
+/// Builds the inline CSS for a style
+///
+/// # Arguments
+///
+/// * `style` - ANSI style to convert
+fn css(style: &Style) -> String {
+    let (mut fg, mut bg) = (style.fg.map(|c| c.to_hex()), style.bg.map(|c| c.to_hex()));
+    if style.reverse {
+        let reversed_fg = bg.unwrap_or_else(|| "#000000".to_string());
+        let reversed_bg = fg.unwrap_or_else(|| "#ffffff".to_string());
+        fg = Some(reversed_fg);
+        bg = Some(reversed_bg);
+    }
+
+    let mut rules: Vec<String> = Vec::new();
+    if let Some(fg) = fg {
+        rules.push(format!("color:{}", fg));
+    }
+
+    if let Some(bg) = bg {
+        rules.push(format!("background-color:{}", bg));
+    }
+
+    if style.bold {
+        rules.push("font-weight:bold".to_string());
+    }
+
+    if style.dim {
+        rules.push("opacity:0.7".to_string());
+    }
+
+    if style.italic {
+        rules.push("font-style:italic".to_string());
+    }
+
+    match (style.underline, style.strike) {
+        (true, true) => rules.push("text-decoration:underline line-through".to_string()),
+        (true, false) => rules.push("text-decoration:underline".to_string()),
+        (false, true) => rules.push("text-decoration:line-through".to_string()),
+        (false, false) => {}
+    }
+
+    rules.join(";")
+}
+
+/// Renders text containing ANSI escape sequences as a standalone HTML
+/// fragment.  The text is wrapped in a `<pre>` block and each styled run
+/// is wrapped in a `<span>` with inline CSS.  All text is HTML-escaped
+///
+/// # Arguments
+///
+/// * `text` - Rendered motd to convert
+pub fn from_ansi(text: &str) -> String {
+    let mut html = String::from("<pre class=\"motd\">");
+
+    for (style, run) in ansi::segments(text) {
+        if style.is_plain() {
+            html.push_str(&escape(&run));
+        } else {
+            html.push_str(&format!(
+                "<span style=\"{}\">{}</span>",
+                css(&style),
+                escape(&run)
+            ));
+        }
+    }
+
+    html.push_str("</pre>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_special_characters() {
+        assert_eq!(
+            escape(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+        assert_eq!(escape("plain"), "plain");
+    }
+
+    #[test]
+    fn wraps_styled_runs_in_spans() {
+        assert_eq!(
+            from_ansi("up \x1b[1;31m<5>\x1b[0m days"),
+            "<pre class=\"motd\">up <span style=\"color:#cd0000;font-weight:bold\">&lt;5&gt;</span> days</pre>"
+        );
+    }
+
+    #[test]
+    fn converts_truecolor_and_reverse() {
+        assert_eq!(
+            from_ansi("\x1b[38;2;1;2;3;48;5;21mx"),
+            "<pre class=\"motd\"><span style=\"color:#010203;background-color:#0000ff\">x</span></pre>"
+        );
+        assert_eq!(
+            from_ansi("\x1b[7;4;9mx"),
+            "<pre class=\"motd\"><span style=\"color:#000000;background-color:#ffffff;text-decoration:underline line-through\">x</span></pre>"
+        );
+    }
+
+    #[test]
+    fn drops_other_escape_sequences() {
+        assert_eq!(from_ansi("\x1b[2Ja\x1b]b"), "<pre class=\"motd\">ab</pre>");
+    }
+}
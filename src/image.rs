@@ -0,0 +1,426 @@
+//! Converts images into art that can be printed to a terminal.
+//!
+//! ```text
+//! {{ image("logo.png", width=30) }}
+//! {{ image("/usr/share/pixmaps/logo.png", width=40, mode=braille) }}
+//! ```
+//!
+//! Images are drawn either with half blocks, where each character cell
+//! shows two pixels (the top in the foreground color, the bottom in the
+//! background color), or with braille patterns, where each cell shows a 2x4
+//! grid of dots in a single color.  Colors are written as truecolor and
+//! reduced to what the terminal supports when the motd is printed.  Art can
+//! also be converted ahead of time with `motd art convert`.
+
+use crate::{
+    ansi::{Color, Style, RESET},
+    error::{MotdResult, ParsingError},
+};
+use std::{fs::File, io::Read, path::Path};
+
+/// Width, in cells, of art converted without a width
+pub const DEFAULT_WIDTH: usize = 40;
+
+/// Largest image, in pixels, that is decoded.  Checked before the pixels
+/// are read, so a crafted header can't make us allocate gigabytes
+const MAX_PIXELS: usize = 4096 * 4096;
+
+/// Pixels with less opacity than this are transparent
+const ALPHA_THRESHOLD: f64 = 128.0;
+
+/// How pixels are drawn with characters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Upper half blocks, with a foreground and background color per cell
+    HalfBlock,
+
+    /// Braille patterns, with a single color per cell
+    Braille,
+}
+
+impl Mode {
+    /// Parses a mode from its name (half or braille), returning None if
+    /// the name is not recognized
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "half" => Some(Mode::HalfBlock),
+            "braille" => Some(Mode::Braille),
+            _ => None,
+        }
+    }
+}
+
+/// A decoded image
+#[derive(Clone, Debug)]
+pub struct Image {
+    /// Width in pixels
+    pub width: usize,
+
+    /// Height in pixels
+    pub height: usize,
+
+    /// RGBA value of every pixel, row by row
+    pub pixels: Vec<[u8; 4]>,
+
+    /// Some pixels are transparent
+    pub has_alpha: bool,
+}
+
+impl Image {
+    /// Loads a PNG image, converting any pixel format to 8-bit RGBA
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the image
+    pub fn load<P: AsRef<Path>>(path: P) -> MotdResult<Image> {
+        Image::decode(File::open(path)?)
+    }
+
+    /// Decodes a PNG image, converting any pixel format to 8-bit RGBA
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Encoded image
+    pub fn decode<R: Read>(data: R) -> MotdResult<Image> {
+        let mut decoder = png::Decoder::new(data);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+        let mut reader = decoder.read_info()?;
+        let info = reader.info();
+        if (info.width as usize).saturating_mul(info.height as usize) > MAX_PIXELS {
+            return Err(ParsingError::ImageTooLarge.into());
+        }
+
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buf)?;
+        let data = &buf[..frame.buffer_size()];
+
+        // Palettes are expanded by the transformations, so only the number
+        // of channels is left to tell the color types apart
+        let pixels: Vec<[u8; 4]> = match frame.color_type.samples() {
+            4 => data.chunks(4).map(|p| [p[0], p[1], p[2], p[3]]).collect(),
+            3 => data.chunks(3).map(|p| [p[0], p[1], p[2], 255]).collect(),
+            2 => data.chunks(2).map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            _ => data.iter().map(|v| [*v, *v, *v, 255]).collect(),
+        };
+
+        let width = frame.width as usize;
+        let height = frame.height as usize;
+        if width == 0 || height == 0 || pixels.len() < width * height {
+            return Err(ParsingError::InvalidImage.into());
+        }
+
+        let has_alpha = pixels.iter().any(|p| p[3] < 255);
+        Ok(Image {
+            width,
+            height,
+            pixels,
+            has_alpha,
+        })
+    }
+
+    /// Averages the pixels of the image covered by a rectangle, weighting
+    /// colors by their opacity
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Left edge, in fractional pixels
+    /// * `y` - Top edge, in fractional pixels
+    /// * `w` - Width, in fractional pixels
+    /// * `h` - Height, in fractional pixels
+    fn sample(&self, x: f64, y: f64, w: f64, h: f64) -> [f64; 4] {
+        let x0 = (x.floor() as usize).min(self.width - 1);
+        let y0 = (y.floor() as usize).min(self.height - 1);
+        let x1 = ((x + w).ceil() as usize).clamp(x0 + 1, self.width);
+        let y1 = ((y + h).ceil() as usize).clamp(y0 + 1, self.height);
+
+        let mut sum = [0.0; 4];
+        let mut count = 0.0;
+        for py in y0..y1 {
+            for px in x0..x1 {
+                let p = self.pixels[py * self.width + px];
+                let a = p[3] as f64;
+                sum[0] += p[0] as f64 * a;
+                sum[1] += p[1] as f64 * a;
+                sum[2] += p[2] as f64 * a;
+                sum[3] += a;
+                count += 1.0;
+            }
+        }
+
+        if sum[3] == 0.0 {
+            return [0.0; 4];
+        }
+
+        [
+            sum[0] / sum[3],
+            sum[1] / sum[3],
+            sum[2] / sum[3],
+            sum[3] / count,
+        ]
+    }
+
+    /// Scales the image to a grid of pixels `cols` wide, keeping its aspect
+    /// ratio
+    ///
+    /// # Arguments
+    ///
+    /// * `cols` - Number of pixels in each row of the grid
+    fn scale(&self, cols: usize) -> Vec<Vec<[f64; 4]>> {
+        let step = self.width as f64 / cols as f64;
+        let rows = ((self.height as f64 / step).round() as usize).max(1);
+
+        (0..rows)
+            .map(|row| {
+                (0..cols)
+                    .map(|col| self.sample(col as f64 * step, row as f64 * step, step, step))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Converts the image into art `width` cells wide
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Number of character cells in each line of the art
+    /// * `mode` - How pixels are drawn
+    /// * `color` - Color the art.  Half blocks can't be drawn without
+    ///   color, so they are drawn as braille instead
+    pub fn to_art(&self, width: usize, mode: Mode, color: bool) -> String {
+        let width = width.max(1);
+        match mode {
+            Mode::HalfBlock if color => self.half_blocks(width),
+            _ => self.braille(width, color),
+        }
+    }
+
+    /// Draws the image with half blocks, two pixels per cell
+    fn half_blocks(&self, width: usize) -> String {
+        let grid = self.scale(width);
+        let transparent = [0.0; 4];
+
+        let mut lines = Vec::new();
+        for pair in grid.chunks(2) {
+            let mut line = String::new();
+            let mut last = Style::default();
+
+            for col in 0..width {
+                let top = pair[0][col];
+                let bottom = pair.get(1).map_or(transparent, |row| row[col]);
+
+                let (c, style) = match (opaque(top), opaque(bottom)) {
+                    (false, false) => (' ', Style::default()),
+                    (true, false) => ('▀', fg(top)),
+                    (false, true) => ('▄', fg(bottom)),
+                    (true, true) => (
+                        '▀',
+                        Style {
+                            bg: Some(rgb(bottom)),
+                            ..fg(top)
+                        },
+                    ),
+                };
+
+                if style != last {
+                    if !last.is_plain() {
+                        line.push_str(RESET);
+                    }
+                    line.push_str(&style.sgr());
+                    last = style;
+                }
+                line.push(c);
+            }
+
+            if !last.is_plain() {
+                line.push_str(RESET);
+            }
+            lines.push(line.trim_end().to_string());
+        }
+
+        lines.join("\n")
+    }
+
+    /// Draws the image with braille patterns, eight dots per cell.  Dots
+    /// are shown for opaque pixels of images with transparency, and for
+    /// pixels brighter than average in other images
+    fn braille(&self, width: usize, color: bool) -> String {
+        // Bit of each dot in a braille pattern, by row and column
+        const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+        let grid = self.scale(width * 2);
+        let opaque_pixels: Vec<f64> = grid
+            .iter()
+            .flatten()
+            .filter(|p| opaque(**p))
+            .map(|p| luminance(*p))
+            .collect();
+        let average = opaque_pixels.iter().sum::<f64>() / opaque_pixels.len().max(1) as f64;
+        let lit = |p: [f64; 4]| opaque(p) && (self.has_alpha || luminance(p) >= average);
+
+        let mut lines = Vec::new();
+        for rows in grid.chunks(4) {
+            let mut line = String::new();
+            let mut last = Style::default();
+
+            for col in 0..width {
+                let mut bits = 0;
+                let mut sum = [0.0; 4];
+                for (dy, row) in rows.iter().enumerate() {
+                    for (dx, bit) in DOTS[dy].iter().enumerate() {
+                        let p = row[col * 2 + dx];
+                        if lit(p) {
+                            bits |= bit;
+                            sum.iter_mut().zip(p.iter()).for_each(|(s, v)| *s += v);
+                        }
+                    }
+                }
+
+                let dots = bits.count_ones() as f64;
+                let style = if bits > 0 && color {
+                    fg([sum[0] / dots, sum[1] / dots, sum[2] / dots, 255.0])
+                } else {
+                    Style::default()
+                };
+
+                if style != last {
+                    if !last.is_plain() {
+                        line.push_str(RESET);
+                    }
+                    line.push_str(&style.sgr());
+                    last = style;
+                }
+                line.push(char::from_u32(0x2800 + bits).unwrap_or(' '));
+            }
+
+            if !last.is_plain() {
+                line.push_str(RESET);
+            }
+            lines.push(line.trim_end_matches('\u{2800}').to_string());
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// Returns true if a sampled pixel is opaque enough to draw
+fn opaque(p: [f64; 4]) -> bool {
+    p[3] >= ALPHA_THRESHOLD
+}
+
+/// Returns the perceived brightness of a sampled pixel
+fn luminance(p: [f64; 4]) -> f64 {
+    0.2126 * p[0] + 0.7152 * p[1] + 0.0722 * p[2]
+}
+
+/// Returns the color of a sampled pixel
+fn rgb(p: [f64; 4]) -> Color {
+    Color::Rgb(p[0].round() as u8, p[1].round() as u8, p[2].round() as u8)
+}
+
+/// Returns a style with the color of a sampled pixel as its foreground
+fn fg(p: [f64; 4]) -> Style {
+    Style {
+        fg: Some(rgb(p)),
+        ..Style::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes pixels as an 8-bit PNG
+    fn png(width: u32, height: u32, color: png::ColorType, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut out, width, height);
+            encoder.set_color(color);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(data).unwrap();
+        }
+        out
+    }
+
+    fn sgr(fg: (u8, u8, u8), bg: Option<(u8, u8, u8)>) -> String {
+        Style {
+            fg: Some(Color::Rgb(fg.0, fg.1, fg.2)),
+            bg: bg.map(|(r, g, b)| Color::Rgb(r, g, b)),
+            ..Style::default()
+        }
+        .sgr()
+    }
+
+    #[test]
+    fn decodes_pixel_formats() {
+        let img = Image::decode(&png(1, 1, png::ColorType::Rgb, &[1, 2, 3])[..]).unwrap();
+        assert_eq!(img.pixels, vec![[1, 2, 3, 255]]);
+        assert!(!img.has_alpha);
+
+        let data = png(2, 1, png::ColorType::GrayscaleAlpha, &[9, 255, 7, 0]);
+        let img = Image::decode(&data[..]).unwrap();
+        assert_eq!((img.width, img.height), (2, 1));
+        assert_eq!(img.pixels, vec![[9, 9, 9, 255], [7, 7, 7, 0]]);
+        assert!(img.has_alpha);
+
+        assert!(Image::decode(&b"not a png"[..]).is_err());
+    }
+
+    #[test]
+    fn draws_half_blocks() {
+        #[rustfmt::skip]
+        let data = [
+            255, 0, 0, 255,    0, 0, 255, 255,
+            0, 255, 0, 255,    0, 0, 0, 0,
+        ];
+        let img = Image::decode(&png(2, 2, png::ColorType::Rgba, &data)[..]).unwrap();
+
+        assert_eq!(
+            img.to_art(2, Mode::HalfBlock, true),
+            format!(
+                "{}▀{}{}▀{}",
+                sgr((255, 0, 0), Some((0, 255, 0))),
+                RESET,
+                sgr((0, 0, 255), None),
+                RESET
+            )
+        );
+        // At one cell wide, the whole image is averaged into a single pixel
+        assert_eq!(
+            img.to_art(1, Mode::HalfBlock, true),
+            format!("{}▀{}", sgr((85, 85, 85), None), RESET)
+        );
+    }
+
+    #[test]
+    fn draws_braille() {
+        // A bright column next to a dark one, four pixels tall
+        let data = [255, 0].repeat(4);
+        let img = Image::decode(&png(2, 4, png::ColorType::Grayscale, &data)[..]).unwrap();
+
+        assert_eq!(img.to_art(1, Mode::Braille, false), "⡇");
+        assert_eq!(
+            img.to_art(1, Mode::Braille, true),
+            format!("{}⡇{}", sgr((255, 255, 255), None), RESET)
+        );
+        assert_eq!(img.to_art(1, Mode::HalfBlock, false), "⡇");
+    }
+
+    #[test]
+    fn refuses_oversized_images() {
+        let mut out = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut out, 5000, 5000);
+            encoder.set_color(png::ColorType::Grayscale);
+            let mut writer = encoder.write_header().unwrap();
+            // Only the header is read before the size is checked
+            writer.write_chunk(png::chunk::IDAT, &[]).unwrap();
+        }
+
+        match Image::decode(&out[..]) {
+            Err(crate::error::Error::ParsingFailed(ParsingError::ImageTooLarge)) => {}
+            other => panic!("expected ImageTooLarge, got {:?}", other),
+        }
+    }
+}
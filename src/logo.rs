@@ -0,0 +1,235 @@
+//! Distribution logos, chosen automatically from os-release.
+//!
+//! ```text
+//! {{ distro_logo }}
+//! {{ distro_logo(arch) }}
+//! ```
+//!
+//! Logos are found by the distribution's `ID`, then each of its `ID_LIKE`
+//! entries, so derivatives get their parent's logo (e.g., Linux Mint gets
+//! Ubuntu's).  Distributions without a logo get Tux.
+//!
+//! A logo is plain text where `${c1}` through `${c4}` switch colors.  Each
+//! color is styled with the theme's `logo1` through `logo4` roles, or the
+//! distribution's own colors if the theme doesn't define them.  Logos in the
+//! logo directories (e.g., `logos/debian.txt`) take precedence over the
+//! bundled ones.
+
+use crate::{
+    ansi::{self, RESET},
+    theme::Theme,
+};
+use std::{fs, path::PathBuf};
+
+/// Directories searched, in order, for a logo by distribution ID
+pub const LOGO_DIRS: [&str; 3] = ["logos", "/etc/motd-rs/logos", "/usr/share/motd-rs/logos"];
+
+/// Logo used when no other matches
+pub const FALLBACK_LOGO: &str = "linux";
+
+/// Bundled logos: distribution ID, art and default colors
+const BUNDLED_LOGOS: [(&str, &str, &[&str]); 7] = [
+    (
+        "alpine",
+        include_str!("../logos/alpine.txt"),
+        &["blue", "white"],
+    ),
+    ("arch", include_str!("../logos/arch.txt"), &["cyan"]),
+    (
+        "debian",
+        include_str!("../logos/debian.txt"),
+        &["red", "white"],
+    ),
+    (
+        "fedora",
+        include_str!("../logos/fedora.txt"),
+        &["blue", "white"],
+    ),
+    (
+        "linux",
+        include_str!("../logos/linux.txt"),
+        &["white", "bright_white", "yellow"],
+    ),
+    ("rhel", include_str!("../logos/rhel.txt"), &["red", "white"]),
+    (
+        "ubuntu",
+        include_str!("../logos/ubuntu.txt"),
+        &["bright_red", "red"],
+    ),
+];
+
+/// Number of colors a logo may use
+const MAX_COLORS: usize = 4;
+
+/// A distribution logo
+#[derive(Clone, Debug)]
+pub struct Logo {
+    /// Distribution ID the logo is for
+    pub id: String,
+
+    /// Art, including color markers
+    pub art: String,
+
+    /// Default style of each color
+    pub colors: Vec<String>,
+}
+
+impl Logo {
+    /// Finds the logo for the first distribution ID that has one, falling
+    /// back to Tux
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - Distribution IDs to look for, in order of preference
+    pub fn find(ids: &[&str]) -> Logo {
+        ids.iter()
+            .chain(std::iter::once(&FALLBACK_LOGO))
+            .find_map(|id| Logo::get(id))
+            .expect("fallback logo is bundled")
+    }
+
+    /// Returns the logo for a distribution ID, if there is one
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Distribution ID (e.g., `debian`)
+    pub fn get(id: &str) -> Option<Logo> {
+        let bundled = BUNDLED_LOGOS.iter().find(|(logo, _, _)| *logo == id);
+        let colors = bundled
+            .map(|(_, _, colors)| colors.iter().map(|c| format!("fg={}", c)).collect())
+            .unwrap_or_default();
+
+        // IDs are names, so don't let one wander out of the logo directories
+        let custom = if id.contains('/') || id.starts_with('.') {
+            None
+        } else {
+            LOGO_DIRS
+                .iter()
+                .map(|dir| PathBuf::from(dir).join(format!("{}.txt", id)))
+                .find_map(|path| fs::read_to_string(path).ok())
+        };
+
+        let art = custom.or_else(|| bundled.map(|(_, art, _)| art.to_string()))?;
+        Some(Logo {
+            id: id.to_string(),
+            art,
+            colors,
+        })
+    }
+
+    /// Renders the logo, coloring it with the theme and padding every line
+    /// to the same width so text can be placed beside it
+    ///
+    /// # Arguments
+    ///
+    /// * `theme` - Theme to color the logo with
+    pub fn render(&self, theme: &Theme) -> String {
+        let styles: Vec<String> = (0..MAX_COLORS)
+            .map(|i| {
+                let mut style = theme.resolve(&format!("logo{}", i + 1));
+                if style.is_plain() {
+                    style = theme.resolve(self.colors.get(i).map_or("", String::as_str));
+                }
+                style.sgr()
+            })
+            .collect();
+
+        let lines: Vec<String> = self
+            .art
+            .trim_end_matches('\n')
+            .lines()
+            .map(|line| {
+                let mut s = String::new();
+                let mut styled = false;
+                let mut rest = line;
+
+                while let Some(start) = rest.find("${c") {
+                    s.push_str(&rest[..start]);
+                    let marker = &rest[start + 3..];
+                    let color = marker
+                        .find('}')
+                        .and_then(|end| marker[..end].parse::<usize>().ok().map(|n| (n, end)));
+
+                    match color {
+                        Some((n, end)) if (1..=MAX_COLORS).contains(&n) => {
+                            if styled {
+                                s.push_str(RESET);
+                            }
+                            s.push_str(&styles[n - 1]);
+                            styled = !styles[n - 1].is_empty();
+                            rest = &marker[end + 1..];
+                        }
+                        _ => {
+                            s.push_str("${c");
+                            rest = marker;
+                        }
+                    }
+                }
+
+                s.push_str(rest);
+                if styled {
+                    s.push_str(RESET);
+                }
+                s
+            })
+            .collect();
+
+        let width = lines.iter().map(|l| ansi::width(l)).max().unwrap_or(0);
+        lines
+            .iter()
+            .map(|line| ansi::pad(line, width, false))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logo(art: &str) -> Logo {
+        Logo {
+            id: "test".to_string(),
+            art: art.to_string(),
+            colors: vec!["fg=red".to_string(), "fg=blue".to_string()],
+        }
+    }
+
+    #[test]
+    fn substitutes_colors() {
+        assert_eq!(
+            logo("${c1}ab${c2}c\nd${c3}e\n\n").render(&Theme::default()),
+            "\x1b[31mab\x1b[0m\x1b[34mc\x1b[0m\nde "
+        );
+        assert_eq!(
+            logo("${c2}a${c3}b").render(&Theme::default()),
+            "\x1b[34ma\x1b[0mb"
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_markers() {
+        assert_eq!(
+            logo("a${c5}b${c}c${c1").render(&Theme::default()),
+            "a${c5}b${c}c${c1"
+        );
+    }
+
+    #[test]
+    fn prefers_theme_colors() {
+        let theme: Theme = toml::from_str("[roles]\nlogo2 = \"bold\"").unwrap();
+        assert_eq!(
+            logo("${c1}a${c2}b").render(&theme),
+            "\x1b[31ma\x1b[0m\x1b[1mb\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn finds_logos_by_id() {
+        assert_eq!(Logo::find(&["nope", "debian", "arch"]).id, "debian");
+        assert_eq!(Logo::find(&["nope"]).id, FALLBACK_LOGO);
+        assert_eq!(Logo::get("arch").unwrap().colors, vec!["fg=cyan"]);
+        assert!(Logo::get("nope").is_none());
+        assert!(Logo::get("../logos/arch").is_none());
+    }
+}
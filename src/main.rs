@@ -2,12 +2,33 @@
 //!
 //! Parses motd templates (*.motd) and displays them to the console
 
+mod ansi;
 mod commands;
+mod config;
+mod cowsay;
 mod error;
+mod export;
+mod figlet;
+mod fortune;
+mod gradient;
+mod html;
+mod image;
+mod logo;
 mod motd;
+mod panel;
+mod plugin;
+mod script;
+mod table;
+mod template;
+mod term;
+mod theme;
+mod threshold;
+mod value;
+mod widget;
 
-use clap::{App, Arg, ArgMatches};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use log::{error, LevelFilter};
+use std::process;
 
 /// Setups the up the command line arguments to process for
 /// generating a message of the day
@@ -21,6 +42,95 @@ fn get_arguments() -> ArgMatches<'static> {
                 .multiple(true)
                 .help("Sets verbosity level, up to -vvvv for everything"),
         )
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Path to the configuration file (default: /etc/motd-rs/config.toml)"),
+        )
+        .arg(
+            Arg::with_name("theme")
+                .short("t")
+                .long("theme")
+                .takes_value(true)
+                .value_name("THEME")
+                .help("Name of, or path to, the theme to render with"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["text", "html"])
+                .default_value("text")
+                .help("Output format of the rendered motd"),
+        )
+        .arg(
+            Arg::with_name("width")
+                .short("w")
+                .long("width")
+                .takes_value(true)
+                .value_name("COLUMNS")
+                .help("Width of the terminal to fit the motd to (default: detected)"),
+        )
+        .subcommand(
+            SubCommand::with_name("facts")
+                .about("Prints every collected fact as structured data")
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["json", "yaml", "toml"])
+                        .default_value("json")
+                        .help("Format to print facts in"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Exports collected facts for monitoring systems")
+                .arg(
+                    Arg::with_name("prometheus")
+                        .long("prometheus")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .required(true)
+                        .help("Writes metrics to a Prometheus textfile (e.g., for node_exporter)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("art")
+                .about("Tools for creating art to use in templates")
+                .subcommand(
+                    SubCommand::with_name("convert")
+                        .about("Converts an image (PNG) into art for the current terminal")
+                        .arg(
+                            Arg::with_name("image")
+                                .required(true)
+                                .value_name("IMAGE")
+                                .help("Path to the image to convert"),
+                        )
+                        .arg(
+                            Arg::with_name("width")
+                                .short("w")
+                                .long("width")
+                                .takes_value(true)
+                                .value_name("COLUMNS")
+                                .default_value("40")
+                                .help("Width of the art"),
+                        )
+                        .arg(
+                            Arg::with_name("mode")
+                                .short("m")
+                                .long("mode")
+                                .takes_value(true)
+                                .possible_values(&["half", "braille"])
+                                .default_value("half")
+                                .help("Characters to draw the art with"),
+                        ),
+                ),
+        )
         .get_matches()
 }
 
@@ -63,8 +173,122 @@ fn main() {
 
     configure_logging(log_level);
 
-    let motd = motd::Motd::new();
-    let template = motd.render("templates/falcon.motd").unwrap();
+    let config = match args.value_of("config") {
+        Some(path) => config::Config::load(path),
+        None => config::Config::load_default(),
+    };
+
+    let mut config = match config {
+        Ok(config) => config,
+        Err(e) => fail(&format!("Failed to load configuration: {:?}", e)),
+    };
+
+    if let Some(theme) = args.value_of("theme") {
+        config.theme = Some(theme.to_string());
+    }
+
+    let theme = match config.theme.as_deref().map(theme::Theme::find) {
+        Some(Ok(theme)) => theme,
+        Some(Err(e)) => fail(&format!("Failed to load theme: {:?}", e)),
+        None => theme::Theme::default(),
+    };
+
+    let mut caps = term::Capabilities::detect();
+    if let Some(width) = args.value_of("width") {
+        match width.parse() {
+            Ok(width) => caps.width = Some(width),
+            Err(e) => fail(&format!("Invalid width {}: {}", width, e)),
+        }
+    }
+
+    let motd = motd::Motd::new(config).theme(theme).capabilities(caps);
+
+    match args.subcommand() {
+        ("facts", Some(sub)) => print_facts(&motd, sub),
+        ("export", Some(sub)) => export_facts(&motd, sub),
+        ("art", Some(sub)) => match sub.subcommand() {
+            ("convert", Some(sub)) => convert_art(&motd.term, sub),
+            _ => fail(sub.usage()),
+        },
+        _ => {
+            let template = match motd.render("templates/falcon.motd") {
+                Ok(template) => template,
+                Err(e) => fail(&format!("Failed to render template: {:?}", e)),
+            };
+            match args.value_of("format") {
+                Some("html") => println!("{}", html::from_ansi(&template)),
+                _ => println!("{}", motd.term.adapt(&template)),
+            }
+        }
+    }
+}
 
-    println!("{}", template);
+/// Prints all facts in the format requested on the command line
+///
+/// # Arguments
+///
+/// * `motd` - Message of the day to collect facts from
+/// * `args` - Arguments passed to the `facts` subcommand
+fn print_facts(motd: &motd::Motd, args: &ArgMatches) {
+    let format = args
+        .value_of("format")
+        .and_then(motd::Format::from_name)
+        .unwrap_or(motd::Format::Json);
+
+    match motd.facts().serialize(format) {
+        Ok(facts) => println!("{}", facts),
+        Err(e) => fail(&format!("Failed to serialize facts: {:?}", e)),
+    }
+}
+
+/// Exports all facts to the destinations requested on the command line
+///
+/// # Arguments
+///
+/// * `motd` - Message of the day to collect facts from
+/// * `args` - Arguments passed to the `export` subcommand
+fn export_facts(motd: &motd::Motd, args: &ArgMatches) {
+    if let Some(path) = args.value_of("prometheus") {
+        if let Err(e) = export::write_prometheus(&motd.facts(), path) {
+            fail(&format!("Failed to write {}: {:?}", path, e));
+        }
+    }
+}
+
+/// Converts an image into art and prints it, so it can be pasted into
+/// a template
+///
+/// # Arguments
+///
+/// * `term` - Capabilities of the terminal to convert for
+/// * `args` - Arguments passed to the `art convert` subcommand
+fn convert_art(term: &term::Capabilities, args: &ArgMatches) {
+    let path = args.value_of("image").unwrap_or_default();
+    let width = args
+        .value_of("width")
+        .and_then(|w| w.parse().ok())
+        .unwrap_or(image::DEFAULT_WIDTH);
+    let mode = args
+        .value_of("mode")
+        .and_then(image::Mode::from_name)
+        .unwrap_or(image::Mode::HalfBlock);
+
+    match image::Image::load(path) {
+        Ok(img) => {
+            let color = term.color != term::ColorDepth::None;
+            println!("{}", term.adapt(&img.to_art(width, mode, color)));
+        }
+        Err(e) => fail(&format!("Failed to convert {}: {:?}", path, e)),
+    }
+}
+
+/// Reports an error that stops the command on stderr, then exits with a
+/// failure status
+///
+/// # Arguments
+///
+/// * `msg` - Description of what failed
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1);
 }
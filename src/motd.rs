@@ -1,80 +1,709 @@
 //! Representation of an motd itself
 
-use crate::commands;
-use chrono::{offset::Local, DateTime};
-use regex::{Captures, Regex};
-use std::{
-    fs::File,
-    io::{self, Read},
-    path::Path,
+use crate::{
+    ansi,
+    config::Config,
+    cowsay,
+    error::MotdResult,
+    figlet::{self, Font},
+    fortune::{self, Fortunes},
+    gradient::Gradient,
+    image::{self, Image, Mode},
+    logo::Logo,
+    panel::{Border, Panel},
+    plugin::{Plugins, PLUGIN_DIR},
+    script::Scripts,
+    table::Table,
+    template::{self, Call, Expr, Node},
+    term::{Capabilities, ColorDepth},
+    theme::Theme,
+    threshold::{Severity, Threshold},
+    value,
+    widget::{self, Options},
 };
+use chrono::{offset::Local, DateTime};
+use log::error;
+use net::Net;
+use serde_json::{json, Value};
+use std::{cell::OnceCell, fs::File, io::Read, path::Path};
 
-mod net;
+mod facts;
+pub(crate) mod net;
 mod system;
 mod user;
 
+pub use facts::{Facts, Format};
+use system::{System, DEFAULT_REBOOTS};
+
 pub struct Motd {
     /// Information about the current user
     pub user: user::User,
     pub net: net::Net,
     pub sys: system::System,
     pub date: DateTime<Local>,
+
+    /// User configuration
+    pub config: Config,
+
+    /// Styles for the roles used in templates
+    pub theme: Theme,
+
+    /// Capabilities of the terminal the motd will be shown on
+    pub term: Capabilities,
+
+    /// External commands, loaded the first time a template needs them
+    plugins: OnceCell<Plugins>,
 }
 
 impl Motd {
-    pub fn new() -> Motd {
+    pub fn new(config: Config) -> Motd {
         let now = Local::now();
         Motd {
             user: user::User::new(),
             net: net::Net::new(),
             sys: system::System::new(),
             date: now,
+            config,
+            theme: Theme::default(),
+            term: Capabilities::default(),
+            plugins: OnceCell::new(),
         }
     }
 
+    /// Sets the theme used to style templates
+    ///
+    /// # Arguments
+    ///
+    /// * `theme` - Theme to render with
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Sets the capabilities of the terminal the motd will be shown on,
+    /// which `when` and `layout` blocks use to fit the output to its size
+    ///
+    /// # Arguments
+    ///
+    /// * `term` - Capabilities of the terminal
+    pub fn capabilities(mut self, term: Capabilities) -> Self {
+        self.term = term;
+        self
+    }
+
+    /// Returns all plugins, discovering them on first use
+    fn plugins(&self) -> &Plugins {
+        self.plugins.get_or_init(|| Plugins::discover(PLUGIN_DIR))
+    }
+
+    /// Collects a typed snapshot of every fact available to templates
+    pub fn facts(&self) -> Facts {
+        Facts::collect(self)
+    }
+
     /// Renders an Message of the Day Template
     ///
     /// # Arguments
     ///
     /// * `path` - Path to MotD template
-    pub fn render<P: AsRef<Path>>(&self, path: P) -> Result<String, io::Error> {
-        // Regex to find all commands to substitute
-        let re = Regex::new(r"\{\{ (?P<cmd>[[:alpha:]]+)(\((?P<args>.*)\))? \}\}").unwrap();
-
+    pub fn render<P: AsRef<Path>>(&self, path: P) -> MotdResult<String> {
         // Read template file in
         let mut file = File::open(path)?;
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
 
-        // Replace all command occurances
-        let result = re.replace_all(&contents, |caps: &Captures| self.replace(caps));
+        let nodes = template::parse(&contents)?;
+
+        // Load any functions defined by the template or configuration
+        let sources = template::scripts(&nodes);
+        let scripts = if sources.is_empty() && self.config.script.files.is_empty() {
+            None
+        } else {
+            let mut scripts = Scripts::new(&self.config.script, &self.facts())?;
+            for src in sources {
+                scripts.load(src)?;
+            }
+            Some(scripts)
+        };
+
+        Ok(self.render_nodes(&nodes, scripts.as_ref()))
+    }
+
+    /// Renders parsed template nodes
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes` - Nodes to render
+    /// * `scripts` - Functions defined by scripts, if any
+    fn render_nodes(&self, nodes: &[Node], scripts: Option<&Scripts>) -> String {
+        let mut s = String::new();
+        for node in nodes {
+            match node {
+                Node::Text(text) => s.push_str(text),
+                Node::Expr(expr) => s.push_str(&self.eval(expr, scripts)),
+                Node::Block { tag, args, body } if tag == "gradient" => {
+                    s.push_str(&self.gradient(args, body, scripts));
+                }
+                Node::Block { tag, args, body } if tag == "when" => {
+                    if self.fits(args) {
+                        s.push_str(&self.render_nodes(body, scripts));
+                    }
+                }
+                Node::Block { tag, args, body } if tag == "layout" => {
+                    s.push_str(&self.layout(args, body, scripts));
+                }
+                Node::Block { tag, args, body } => {
+                    let body = self.render_nodes(body, scripts);
+                    s.push_str(&self.block(tag, args, body));
+                }
+                Node::Script(_) => {}
+            }
+        }
+
+        s
+    }
+
+    /// Evaluates an expression, running the command and passing its
+    /// output through each filter.  If the configuration declares a
+    /// threshold for the command, and the expression doesn't use its own,
+    /// the output is colored by severity before any filters are applied
+    ///
+    /// # Arguments
+    ///
+    /// * `expr` - Expression to evaluate
+    /// * `scripts` - Functions defined by scripts, if any
+    fn eval(&self, expr: &Expr, scripts: Option<&Scripts>) -> String {
+        let call = &expr.call;
+        let (mut s, value) = self.replace(&call.name, call.args.as_deref(), scripts);
+
+        if !expr.filters.iter().any(|f| f.name == "threshold") {
+            if let Some(threshold) = self.config.thresholds.get(&call.name) {
+                s = self.severity(&s, threshold.severity(&value));
+            }
+        }
+
+        for filter in expr.filters.iter() {
+            s = self.filter(filter, &value, s);
+        }
+
+        s
+    }
+
+    /// Runs a command, returning the text to replace it with and the
+    /// typed value behind that text
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - Name of the command
+    /// * `args` - Arguments passed to the command, exactly as written
+    /// * `scripts` - Functions defined by scripts, if any
+    fn replace(&self, cmd: &str, args: Option<&str>, scripts: Option<&Scripts>) -> (String, Value) {
+        let text = |s: String| (s.clone(), Value::String(s));
+
+        match cmd {
+            "user" => text(self.user.name.clone()),
+            "tty" => text(self.user.tty.clone()),
+            "full_name" => text(self.user.full_name.clone()),
+            "uid" => (self.user.uid.to_string(), json!(self.user.uid)),
+            "gid" => (self.user.gid.to_string(), json!(self.user.gid)),
+            "home" => text(self.user.home.clone()),
+            "shell" => text(self.user.shell.clone()),
+            "groups" => (self.user.groups.join(", "), json!(self.user.groups)),
+            "login_method" => text(self.user.method.name().to_string()),
+            "remote" => match &self.user.remote {
+                Some(remote) => (user::User::format_remote(remote), json!(remote)),
+                None => (String::new(), Value::Null),
+            },
+            "login_time" => match self.user.login {
+                Some(login) => (
+                    login
+                        .format(args.unwrap_or("%a, %d %b %Y %T %z"))
+                        .to_string(),
+                    json!(login),
+                ),
+                None => (String::new(), Value::Null),
+            },
+            "date" => text(self.date(args)),
+            "uptime" => {
+                let seconds = self.sys.uptime_seconds();
+                (System::format_uptime(seconds), json!(seconds))
+            }
+            "hostname" => text(self.net.hostname()),
+            "fqdn" => text(self.net.fqdn()),
+            "domain" => text(Net::domain(&self.net.fqdn())),
+            "machine_id" | "boot_id" => {
+                let id = match cmd {
+                    "machine_id" => self.sys.machine_id(),
+                    _ => self.sys.boot_id(),
+                };
+                (id.clone().unwrap_or_default(), json!(id))
+            }
+            "os" => match self.sys.os() {
+                Some(os) => (System::format_os(&os), json!(os)),
+                None => (String::new(), Value::Null),
+            },
+            "distro_logo" => {
+                let os = self.sys.os();
+                let mut ids: Vec<&str> = args.map(ansi::unquote).into_iter().collect();
+                if let Some(os) = &os {
+                    ids.push(&os.id);
+                    ids.extend(os.id_like.iter().map(String::as_str));
+                }
+
+                let logo = Logo::find(&ids);
+                (logo.render(&self.theme), Value::String(logo.id))
+            }
+            "users" => {
+                let sessions = self.sys.sessions();
+                let users = System::user_list(&sessions);
+                (System::format_users(&users, sessions.len()), json!(users))
+            }
+            "sessions" => {
+                let sessions = self.sys.sessions();
+                (System::format_sessions(&sessions), json!(sessions))
+            }
+            "last_login" => match self.sys.last_login(&self.user.name) {
+                Some(login) => (System::format_last_login(&login), json!(login)),
+                None => ("never".to_string(), Value::Null),
+            },
+            "failed_logins" => match self.sys.failed_logins(&self.user.name) {
+                Some(failed) => (System::format_failed_logins(&failed), json!(failed)),
+                None => (String::new(), Value::Null),
+            },
+            "reboots" => {
+                let count = args
+                    .and_then(|a| ansi::unquote(a).parse().ok())
+                    .unwrap_or(DEFAULT_REBOOTS);
+                let reboots = self.sys.reboots(count);
+                (System::format_reboots(&reboots), json!(reboots))
+            }
+            "logins" => {
+                let counts = self.sys.login_counts();
+                match args.map(ansi::unquote) {
+                    Some(user) => {
+                        let count = counts.get(user).copied().unwrap_or(0);
+                        (count.to_string(), json!(count))
+                    }
+                    None => (System::format_login_counts(&counts), json!(counts)),
+                }
+            }
+            "ipaddr" => {
+                let interfaces = self.net.ips(args);
+                (Net::format_ips(&interfaces, args), json!(interfaces))
+            }
+            "conns" => {
+                let conns = self.net.connection_counts();
+                (Net::format_connections(&conns), json!(conns))
+            }
+            "process" => {
+                let count = self.sys.process_count();
+                (System::format_processes(count), json!(count))
+            }
+            "disk" => match self.sys.disk(args.map(ansi::unquote).unwrap_or("/")) {
+                Some(disk) => (System::format_disk(&disk), json!(disk)),
+                None => (String::new(), Value::Null),
+            },
+            "mem" | "swap" => match self.sys.memory() {
+                Some((mem, swap)) => {
+                    let mem = if cmd == "mem" { mem } else { swap };
+                    (System::format_memory(&mem), json!(mem))
+                }
+                None => (String::new(), Value::Null),
+            },
+            "cpu" => match self.sys.cpu() {
+                Some(cpu) => (System::format_cpu(&cpu), json!(cpu)),
+                None => (String::new(), Value::Null),
+            },
+            "fortune" => text(self.fortune(args)),
+            "bar" | "gauge" => self.widget(cmd, args.unwrap_or(""), scripts),
+            "table" => self.table(args.unwrap_or(""), scripts),
+            "figlet" => self.figlet(args.unwrap_or(""), scripts),
+            "image" => self.image(args.unwrap_or("")),
+            cmd => {
+                let result = scripts
+                    .and_then(|s| s.call(cmd, args))
+                    .or_else(|| self.plugins().call(cmd, args));
+
+                match result {
+                    Some(Ok(v)) => (value::display(&v), v),
+                    Some(Err(e)) => {
+                        error!("command {} failed: {:?}", cmd, e);
+                        (String::new(), Value::Null)
+                    }
+                    None => {
+                        error!("unknown command {}", cmd);
+                        (String::new(), Value::Null)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draws a bar or gauge widget for a percentage.  The filled part of
+    /// the bar is colored by severity, using the theme's `good` role (green
+    /// if the theme doesn't define it) while the value is normal.  Gauges
+    /// are followed by the percentage
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - Name of the widget (bar or gauge)
+    /// * `args` - Arguments passed to the widget, exactly as written
+    /// * `scripts` - Functions defined by scripts, if any
+    fn widget(&self, kind: &str, args: &str, scripts: Option<&Scripts>) -> (String, Value) {
+        let opts = match Options::parse(args) {
+            Some(opts) => opts,
+            None => {
+                error!(
+                    "{} needs a value to draw, e.g. {}(mem.used_pct)",
+                    kind, kind
+                );
+                return (String::new(), Value::Null);
+            }
+        };
+
+        let call = &opts.value.call;
+        let (_, v) = self.replace(&call.name, call.args.as_deref(), scripts);
+        let v = match (&opts.value.field, &v) {
+            (Some(field), v) => value::lookup(v, field),
+            (None, Value::Object(map)) => map.get("used_pct"),
+            (None, v) => Some(v),
+        };
+
+        let pct = match v.and_then(value::number) {
+            Some(pct) => pct,
+            None => return (String::new(), Value::Null),
+        };
+
+        let threshold = opts
+            .threshold
+            .as_ref()
+            .or_else(|| self.config.thresholds.get(&call.name))
+            .unwrap_or(&widget::DEFAULT_THRESHOLD);
+
+        let width = opts.width.unwrap_or(match kind {
+            "gauge" => widget::GAUGE_WIDTH,
+            _ => widget::BAR_WIDTH,
+        });
+
+        let (filled, empty) = widget::bar(pct, width, self.term.unicode && !opts.ascii);
+        let filled = match threshold.severity(&json!(pct)) {
+            Severity::Normal => {
+                let mut style = self.theme.resolve("good");
+                if style.is_plain() {
+                    style = self.theme.resolve("fg=green");
+                }
+                ansi::paint(&filled, &style)
+            }
+            severity => self.severity(&filled, severity),
+        };
+
+        let mut s = format!("{}{}", filled, empty);
+        if kind == "gauge" {
+            s.push_str(&format!(" {:>3.0}%", pct));
+        }
+
+        (s, json!(pct))
+    }
+
+    /// Draws a table widget, with the header styled by the theme's
+    /// `header` role
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments passed to the widget, exactly as written
+    /// * `scripts` - Functions defined by scripts, if any
+    fn table(&self, args: &str, scripts: Option<&Scripts>) -> (String, Value) {
+        let table = match Table::parse(args) {
+            Some(table) => table,
+            None => {
+                error!("table needs a value to show, e.g. table(ipaddr)");
+                return (String::new(), Value::Null);
+            }
+        };
 
-        Ok(result.to_string())
+        let call = &table.value.call;
+        let (_, v) = self.replace(&call.name, call.args.as_deref(), scripts);
+        let v = match &table.value.field {
+            Some(field) => value::lookup(&v, field).cloned().unwrap_or(Value::Null),
+            None => v,
+        };
+
+        let header_style = self.theme.resolve("header");
+        (table.render(&v, &header_style, self.term.unicode), v)
     }
 
-    /// Performs the replacement for each successfull capture
+    /// Renders text as a FIGlet banner.  The first argument is either
+    /// quoted text or a command whose output to render, optionally
+    /// followed by `font=NAME`
     ///
     /// # Arguments
     ///
-    /// * `cap` - The current captured value, available via the named-group "cmd"
-    fn replace(&self, caps: &Captures) -> String {
-        let cmd = caps.name("cmd").unwrap();
-        let args = caps.name("args").map(|m| m.as_str());
-        match cmd.as_str() {
-            "user" => self.user.name.clone(),
-            "tty" => self.user.tty.clone(),
-            "date" => self.date(args),
-            "uptime" => self.sys.uptime(),
-            "hostname" => self.net.hostname(),
-            "users" => self.sys.users(),
-            "ipaddr" => self.net.ips(args),
-            "conns" => self.net.connections(),
-            "process" => self.sys.processes(),
-            "fortune" => commands::fortune(None),
-            _ => panic!("Unrecognized command!"),
+    /// * `args` - Arguments passed to the command, exactly as written
+    /// * `scripts` - Functions defined by scripts, if any
+    fn figlet(&self, args: &str, scripts: Option<&Scripts>) -> (String, Value) {
+        let mut args = template::split_top_level(args, ',')
+            .into_iter()
+            .map(str::trim);
+        let source = args.next().unwrap_or("");
+
+        let text = match template::parse_ref(source) {
+            Some(r) => {
+                let (text, v) = self.replace(&r.call.name, r.call.args.as_deref(), scripts);
+                match r.field {
+                    Some(field) => value::lookup(&v, &field)
+                        .map(value::display)
+                        .unwrap_or_default(),
+                    None => ansi::strip(&text),
+                }
+            }
+            None => ansi::unquote(source).to_string(),
+        };
+
+        let font = args
+            .find_map(|arg| arg.strip_prefix("font="))
+            .map(ansi::unquote)
+            .unwrap_or(figlet::DEFAULT_FONT);
+
+        match Font::find(font) {
+            Ok(font) => (font.render(&text), Value::String(text)),
+            Err(e) => {
+                error!("failed to load font {}: {:?}", font, e);
+                (text.clone(), Value::String(text))
+            }
         }
     }
 
+    /// Converts an image into art, e.g. `image("logo.png", width=30,
+    /// mode=braille)`.  The art is colored unless the terminal doesn't
+    /// support color
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments passed to the command, exactly as written
+    fn image(&self, args: &str) -> (String, Value) {
+        let mut args = template::split_top_level(args, ',')
+            .into_iter()
+            .map(str::trim);
+        let path = ansi::unquote(args.next().unwrap_or(""));
+
+        let mut width = image::DEFAULT_WIDTH;
+        let mut mode = Mode::HalfBlock;
+        for arg in args {
+            match arg.split_once('=') {
+                Some(("width", w)) => width = w.trim().parse().unwrap_or(width),
+                Some(("mode", m)) => mode = Mode::from_name(ansi::unquote(m)).unwrap_or(mode),
+                _ => error!("unknown image argument {}", arg),
+            }
+        }
+
+        match Image::load(path) {
+            Ok(img) => {
+                let color = self.term.color != ColorDepth::None;
+                (
+                    img.to_art(width, mode, color),
+                    Value::String(path.to_string()),
+                )
+            }
+            Err(e) => {
+                error!("failed to load image {}: {:?}", path, e);
+                (String::new(), Value::Null)
+            }
+        }
+    }
+
+    /// Colors text by severity, using the theme's `warn` and `critical`
+    /// roles (yellow and bold red if the theme doesn't define them)
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Text to color
+    /// * `severity` - How severe the value behind the text is
+    fn severity(&self, text: &str, severity: Severity) -> String {
+        let (role, fallback) = match severity {
+            Severity::Normal => return text.to_string(),
+            Severity::Warn => ("warn", "fg=yellow"),
+            Severity::Critical => ("critical", "fg=red bold"),
+        };
+
+        let mut style = self.theme.resolve(role);
+        if style.is_plain() {
+            style = self.theme.resolve(fallback);
+        }
+
+        ansi::paint(text, &style)
+    }
+
+    /// Applies a filter to the output of a command
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Filter to apply
+    /// * `value` - Typed value behind the command's output
+    /// * `input` - Output of the command (or previous filter)
+    fn filter(&self, filter: &Call, value: &Value, input: String) -> String {
+        let arg = filter.args.as_deref().map(ansi::unquote).unwrap_or("");
+        match filter.name.as_str() {
+            "fg" | "bg" => ansi::paint(
+                &input,
+                &self.theme.resolve(&format!("{}={}", filter.name, arg)),
+            ),
+            "bold" | "dim" | "italic" | "underline" | "reverse" | "strike" => {
+                ansi::paint(&input, &self.theme.resolve(&filter.name))
+            }
+            "style" => ansi::paint(&input, &self.theme.resolve(arg)),
+            "pad" | "lpad" => match arg.parse::<usize>() {
+                Ok(width) => ansi::pad(&input, width, filter.name == "lpad"),
+                Err(_) => input,
+            },
+            "threshold" => self.severity(&input, Threshold::parse(arg).severity(value)),
+            "cowsay" | "cowthink" => {
+                let mut options = cowsay::Options::parse(filter.args.as_deref().unwrap_or(""));
+                options.think |= filter.name == "cowthink";
+                match cowsay::say(&input, &options, self.term.width) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        error!("failed to draw cow {}: {:?}", options.cow, e);
+                        input
+                    }
+                }
+            }
+            name => {
+                error!("unknown filter {}", name);
+                input
+            }
+        }
+    }
+
+    /// Applies a block tag to its rendered body
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - Name of the block tag
+    /// * `args` - Arguments passed to the tag, exactly as written
+    /// * `body` - Rendered body of the block
+    fn block(&self, tag: &str, args: &str, body: String) -> String {
+        match tag {
+            "style" => ansi::paint(&body, &self.theme.resolve(args)),
+            "panel" => {
+                let mut panel = Panel::parse(args, &self.theme);
+                if !self.term.unicode {
+                    panel.border = Border::ASCII;
+                }
+                panel.draw(&body)
+            }
+            tag => {
+                error!("unknown block {}", tag);
+                body
+            }
+        }
+    }
+
+    /// Renders a gradient block.  Only the literal text of the block is
+    /// colored, expressions and nested blocks are rendered as usual
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments passed to the tag, exactly as written
+    /// * `body` - Nodes inside the block
+    /// * `scripts` - Functions defined by scripts, if any
+    fn gradient(&self, args: &str, body: &[Node], scripts: Option<&Scripts>) -> String {
+        let parts: Vec<(bool, String)> = body
+            .iter()
+            .map(|node| match node {
+                Node::Text(text) => (true, text.clone()),
+                node => (
+                    false,
+                    self.render_nodes(std::slice::from_ref(node), scripts),
+                ),
+            })
+            .collect();
+
+        match Gradient::parse(args, &self.theme) {
+            Some(gradient) => gradient.apply(&parts),
+            None => {
+                error!("gradient needs rainbow, or both from and to colors");
+                parts.into_iter().map(|(_, s)| s).collect()
+            }
+        }
+    }
+
+    /// Returns true if the terminal meets every size condition given, e.g.
+    /// `min_width=80 max_height=40`.  An unknown size is treated as
+    /// unlimited, so minimums pass and maximums fail
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Conditions passed to the tag, exactly as written
+    fn fits(&self, args: &str) -> bool {
+        args.split_whitespace().all(|cond| {
+            let (key, limit) = match cond.split_once('=').map(|(k, v)| (k, v.parse::<usize>())) {
+                Some((key, Ok(limit))) => (key, limit),
+                _ => {
+                    error!("invalid condition {}", cond);
+                    return false;
+                }
+            };
+
+            let (size, min) = match key {
+                "min_width" => (self.term.width, true),
+                "max_width" => (self.term.width, false),
+                "min_height" => (self.term.height, true),
+                "max_height" => (self.term.height, false),
+                key => {
+                    error!("unknown condition {}", key);
+                    return false;
+                }
+            };
+
+            match size {
+                Some(size) if min => size >= limit,
+                Some(size) => size <= limit,
+                None => min,
+            }
+        })
+    }
+
+    /// Renders a layout block.  On terminals narrower than `min_width`,
+    /// the first `art_width` columns of the block are removed, leaving
+    /// only the information next to the art
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments passed to the tag, exactly as written
+    /// * `body` - Nodes inside the block
+    /// * `scripts` - Functions defined by scripts, if any
+    fn layout(&self, args: &str, body: &[Node], scripts: Option<&Scripts>) -> String {
+        let mut art_width = None;
+        let mut conds = Vec::new();
+        for arg in args.split_whitespace() {
+            match arg.strip_prefix("art_width=").map(str::parse::<usize>) {
+                Some(Ok(width)) => art_width = Some(width),
+                Some(Err(_)) => error!("invalid art_width {}", arg),
+                None => conds.push(arg),
+            }
+        }
+
+        match art_width {
+            Some(width) if !self.fits(&conds.join(" ")) => {
+                self.render_nodes(&template::drop_columns(body, width), scripts)
+            }
+            _ => self.render_nodes(body, scripts),
+        }
+    }
+
+    /// Chooses a fortune, using the configured fortune settings overridden
+    /// by any arguments
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments passed to the command, if any
+    pub fn fortune(&self, args: Option<&str>) -> String {
+        Fortunes::new(&self.config.fortune)
+            .args(args.unwrap_or(""))
+            .choose()
+            .unwrap_or_else(|| fortune::NO_FORTUNE.to_string())
+    }
+
     /// Returns the current date and time, formatted as specified by the
     /// user, or via the default format
     pub fn date(&self, fmt: Option<&str>) -> String {
@@ -83,3 +712,102 @@ impl Motd {
             .to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: Option<&str>) -> Call {
+        Call {
+            name: name.to_string(),
+            args: args.map(String::from),
+        }
+    }
+
+    #[test]
+    fn applies_filters() {
+        let motd = Motd::new(Config::default());
+        let input = || "ab".to_string();
+        assert_eq!(
+            motd.filter(&call("fg", Some("\"red\"")), &Value::Null, input()),
+            "\x1b[31mab\x1b[0m"
+        );
+        assert_eq!(
+            motd.filter(&call("bold", None), &Value::Null, input()),
+            "\x1b[1mab\x1b[0m"
+        );
+        assert_eq!(
+            motd.filter(&call("style", Some("'bg=1 italic'")), &Value::Null, input()),
+            "\x1b[3;41mab\x1b[0m"
+        );
+        assert_eq!(
+            motd.filter(&call("pad", Some("4")), &Value::Null, input()),
+            "ab  "
+        );
+        assert_eq!(
+            motd.filter(&call("lpad", Some("4")), &Value::Null, input()),
+            "  ab"
+        );
+        assert_eq!(
+            motd.filter(&call("pad", Some("x")), &Value::Null, input()),
+            "ab"
+        );
+        assert_eq!(
+            motd.filter(&call("sparkle", None), &Value::Null, input()),
+            "ab"
+        );
+    }
+
+    #[test]
+    fn colors_widgets_by_severity() {
+        let motd = Motd::new(Config::default());
+        let mut scripts = Scripts::new(&motd.config.script, &motd.facts()).unwrap();
+        scripts
+            .load("fn pct(n) { parse_float(n) } fn usage() { #{ used_pct: 95.0 } }")
+            .unwrap();
+        let widget = |kind, args| motd.widget(kind, args, Some(&scripts)).0;
+
+        assert_eq!(widget("bar", "pct(50), width=4"), "\x1b[32m██\x1b[0m░░");
+        assert_eq!(
+            widget("bar", "pct(85), width=4, ascii"),
+            "\x1b[33m###\x1b[0m-"
+        );
+        assert_eq!(
+            widget("gauge", "usage, width=2"),
+            "\x1b[1;31m█▉\x1b[0m  95%"
+        );
+        assert_eq!(
+            widget("bar", "pct(10), width=2, below, critical=20"),
+            "\x1b[1;31m▎\x1b[0m░"
+        );
+        assert_eq!(widget("bar", "usage.missing"), "");
+        assert_eq!(widget("bar", ""), "");
+    }
+
+    #[test]
+    fn colors_by_severity() {
+        let motd = Motd::new(Config::default());
+        let threshold = call("threshold", Some("warn=80, critical=95"));
+        let filter = |v: Value| motd.filter(&threshold, &v, "ab".to_string());
+        assert_eq!(filter(json!(50)), "ab");
+        assert_eq!(filter(json!(90)), "\x1b[33mab\x1b[0m");
+        assert_eq!(filter(json!(99)), "\x1b[1;31mab\x1b[0m");
+
+        let theme: Theme = toml::from_str("[roles]\nwarn = \"fg=magenta\"").unwrap();
+        let motd = Motd::new(Config::default()).theme(theme);
+        assert_eq!(
+            motd.filter(&threshold, &json!(90), "ab".to_string()),
+            "\x1b[35mab\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn applies_blocks() {
+        let motd = Motd::new(Config::default());
+        assert_eq!(
+            motd.block("style", "fg=green", "a\nb".to_string()),
+            "\x1b[32ma\x1b[0m\n\x1b[32mb\x1b[0m"
+        );
+        assert_eq!(motd.block("sparkle", "", "a".to_string()), "a");
+    }
+}
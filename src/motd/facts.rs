@@ -0,0 +1,208 @@
+//! Typed snapshot of every fact the motd can display
+
+use crate::{
+    error::MotdResult,
+    motd::{
+        net::{Connections, Interface, Net},
+        system::{
+            Disk, FailedLogins, LastLogin, Memory, OsRelease, Reboot, Session, System,
+            DEFAULT_REBOOTS,
+        },
+        user::{LoginMethod, Remote},
+        Motd,
+    },
+};
+use chrono::{offset::Local, DateTime};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Formats facts can be serialized to
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// Parses a format from it's name (json, yaml, or toml), returning
+    /// None if the name is not recognized
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "yaml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// All facts collected about this system, stored as typed data instead
+/// of the formatted strings used when rendering a template
+#[derive(Clone, Debug, Serialize)]
+pub struct Facts {
+    /// Username of the current user
+    pub user: String,
+
+    /// User ID of the current user
+    pub uid: u32,
+
+    /// ID of the current user's primary group
+    pub gid: u32,
+
+    /// Full name of the current user, if the passwd database has it
+    pub full_name: String,
+
+    /// Home directory of the current user
+    pub home: String,
+
+    /// Login shell of the current user
+    pub shell: String,
+
+    /// Groups the current user belongs to, primary group first
+    pub groups: Vec<String>,
+
+    /// TTY the current user is logged in on
+    pub tty: String,
+
+    /// How the current user logged in
+    pub login_method: LoginMethod,
+
+    /// Where the current user logged in from, for remote logins
+    pub remote: Option<Remote>,
+
+    /// Time the current user's session started, if known
+    pub login_time: Option<DateTime<Local>>,
+
+    /// Hostname of this computer, without its domain
+    pub hostname: String,
+
+    /// Fully qualified domain name of this computer
+    pub fqdn: String,
+
+    /// DNS domain of this computer, empty if it isn't in one
+    pub domain: String,
+
+    /// ID of this machine (e.g., from `/etc/machine-id`)
+    pub machine_id: Option<String>,
+
+    /// ID of the current boot
+    pub boot_id: Option<String>,
+
+    /// Operating system, if it could be identified
+    pub os: Option<OsRelease>,
+
+    /// Time these facts were collected
+    pub date: DateTime<Local>,
+
+    /// Number of seconds since the last reboot
+    pub uptime: u64,
+
+    /// Names of all logged in users
+    pub users: Vec<String>,
+
+    /// Open login sessions, oldest first
+    pub sessions: Vec<Session>,
+
+    /// Current user's last login before this session, if any
+    pub last_login: Option<LastLogin>,
+
+    /// Failed attempts to log in as the current user since their last
+    /// login, if btmp could be read
+    pub failed_logins: Option<FailedLogins>,
+
+    /// Most recent reboots, newest first
+    pub reboots: Vec<Reboot>,
+
+    /// Number of times each user has logged in since wtmp was rotated
+    pub logins: BTreeMap<String, usize>,
+
+    /// Number of running processes
+    pub processes: usize,
+
+    /// Number of listening and established TCP connections
+    pub connections: Connections,
+
+    /// All network interfaces and their addresses
+    pub interfaces: Vec<Interface>,
+
+    /// Usage of the root filesystem
+    pub disk: Option<Disk>,
+
+    /// Usage of physical memory
+    pub memory: Option<Memory>,
+
+    /// Usage of swap
+    pub swap: Option<Memory>,
+
+    /// Fortune of the day
+    pub fortune: String,
+}
+
+impl Facts {
+    /// Collects all facts using the same providers used to render
+    /// a template
+    ///
+    /// # Arguments
+    ///
+    /// * `motd` - Message of the day to collect facts from
+    pub fn collect(motd: &Motd) -> Facts {
+        let (memory, swap) = match motd.sys.memory() {
+            Some((memory, swap)) => (Some(memory), Some(swap)),
+            None => (None, None),
+        };
+
+        let sessions = motd.sys.sessions();
+        let fqdn = motd.net.fqdn();
+
+        Facts {
+            user: motd.user.name.clone(),
+            uid: motd.user.uid,
+            gid: motd.user.gid,
+            full_name: motd.user.full_name.clone(),
+            home: motd.user.home.clone(),
+            shell: motd.user.shell.clone(),
+            groups: motd.user.groups.clone(),
+            tty: motd.user.tty.clone(),
+            login_method: motd.user.method,
+            remote: motd.user.remote.clone(),
+            login_time: motd.user.login,
+            hostname: motd.net.hostname(),
+            domain: Net::domain(&fqdn),
+            fqdn,
+            machine_id: motd.sys.machine_id(),
+            boot_id: motd.sys.boot_id(),
+            os: motd.sys.os(),
+            date: motd.date,
+            uptime: motd.sys.uptime_seconds(),
+            users: System::user_list(&sessions),
+            sessions,
+            last_login: motd.sys.last_login(&motd.user.name),
+            failed_logins: motd.sys.failed_logins(&motd.user.name),
+            reboots: motd.sys.reboots(DEFAULT_REBOOTS),
+            logins: motd.sys.login_counts(),
+            processes: motd.sys.process_count(),
+            connections: motd.net.connection_counts(),
+            interfaces: motd.net.interfaces(false, false, false),
+            disk: motd.sys.disk("/"),
+            memory,
+            swap,
+            fortune: motd.fortune(None),
+        }
+    }
+
+    /// Serializes all facts to the requested format
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - Format to serialize to
+    pub fn serialize(&self, format: Format) -> MotdResult<String> {
+        let s = match format {
+            Format::Json => serde_json::to_string_pretty(self)?,
+            Format::Yaml => serde_yaml::to_string(self)?,
+            Format::Toml => toml::to_string_pretty(&toml::Value::try_from(self)?)?,
+        };
+
+        Ok(s)
+    }
+}
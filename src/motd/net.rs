@@ -1,96 +1,198 @@
 //! Networking related information
 
 use crate::commands;
+use log::error;
+use serde::Serialize;
+use std::cell::OnceCell;
 
 /// All networking-related fields, to include interfaces,
 /// IP addresses, hostnames, etc.
 #[derive(Default)]
-pub struct Net;
+pub struct Net {
+    /// Hostname as configured, possibly qualified, or empty if it can't
+    /// be read
+    hostname: OnceCell<String>,
+
+    /// Fully qualified domain name, or empty if there is no hostname
+    fqdn: OnceCell<String>,
+}
+
+/// A network interface and the addresses assigned to it
+#[derive(Clone, Debug, Serialize)]
+pub struct Interface {
+    /// Name of the interface (e.g., eth0)
+    pub name: String,
+
+    /// All IPv4 addresses assigned to this interface
+    pub addrs: Vec<String>,
+}
+
+/// Number of TCP sockets in the listening and established states
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Connections {
+    /// Sockets waiting for incoming connections
+    pub listening: usize,
+
+    /// Sockets with an established connection
+    pub established: usize,
+}
 
 impl Net {
     pub fn new() -> Net {
         Net::default()
     }
 
-    /// Returns the hostname for this computer, or localhost if
-    /// a hostname is not set or the command fails
-    pub fn hostname(&self) -> String {
-        match commands::hostname(None) {
+    /// Returns the hostname as configured, reading it the first time it is
+    /// called
+    fn configured_hostname(&self) -> &str {
+        self.hostname.get_or_init(|| match commands::hostname() {
             Ok(host) => host,
-            Err(_) => "localhost".to_string(),
-        }
+            Err(e) => {
+                error!("failed to read hostname: {:?}", e);
+                String::new()
+            }
+        })
+    }
+
+    /// Returns the short hostname for this computer, without its domain,
+    /// or an empty string if the hostname can't be read
+    pub fn hostname(&self) -> String {
+        let host = self.configured_hostname();
+        host.split('.').next().unwrap_or("").to_string()
     }
 
-    /// Returns a formatted list of IPs assocated with network interfaces
+    /// Returns the fully qualified domain name of this computer, from
+    /// `/etc/hosts` or DNS, falling back to the hostname as configured.
+    /// Returns an empty string if the hostname can't be read
+    pub fn fqdn(&self) -> String {
+        self.fqdn
+            .get_or_init(|| {
+                let host = self.configured_hostname();
+                if host.is_empty() {
+                    return String::new();
+                }
+                commands::fqdn(host).unwrap_or_else(|| host.to_string())
+            })
+            .clone()
+    }
+
+    /// Returns the DNS domain of this computer (e.g., `example.com`), or an
+    /// empty string if it isn't in one
+    ///
+    /// # Arguments
+    ///
+    /// * `fqdn` - Fully qualified domain name of this computer
+    pub fn domain(fqdn: &str) -> String {
+        fqdn.split_once('.')
+            .map(|(_, domain)| domain.to_string())
+            .unwrap_or_default()
+    }
+
+    /// Returns the network interfaces that have addresses, filtered as
+    /// requested
     ///
     /// Arguments: Comma-separated list of strings
-    /// * `name_only` - Only show interface names, not IP addresses
-    /// * `addr_only` - Only show interface ips, not names
     /// * `hide_loopback` - Hides the loopback address
     /// * `hide_private` - Hide all private ips (10.0.0.0/8, 172.16.0.0/12, 192.168.0.0/16)
     /// * `hide_public` - Only show private ips
     ///
-    pub fn ips(&self, args: Option<&str>) -> String {
-        let mut s = String::new();
-
-        let mut show_name = true;
-        let mut show_addr = true;
-        let mut hide_loopback = false;
-        let mut hide_private = false;
-        let mut hide_public = false;
-
-        if let Some(arg_str) = args {
-            for arg in arg_str.split(',') {
-                if arg == "name_only" {
-                    show_addr = false;
-                }
-
-                if arg == "addr_only" {
-                    show_name = false;
-                }
-
-                if arg == "hide_loopback" {
-                    hide_loopback = true;
-                }
+    pub fn ips(&self, args: Option<&str>) -> Vec<Interface> {
+        self.interfaces(
+            has_flag(args, "hide_loopback"),
+            has_flag(args, "hide_public"),
+            has_flag(args, "hide_private"),
+        )
+        .into_iter()
+        .filter(|interface| !interface.addrs.is_empty())
+        .collect()
+    }
 
-                if arg == "hide_private" {
-                    hide_private = true;
-                }
+    /// Formats a list of IPv4 addresses assocated with network interfaces
+    /// (e.g., `eth0: 10.0.0.2, 10.0.0.3; lo: 127.0.0.1`).  Use the `table`
+    /// widget to show them in columns instead
+    ///
+    /// Arguments: Comma-separated list of strings
+    /// * `name_only` - Only show interface names, not IP addresses
+    /// * `addr_only` - Only show interface ips, not names
+    ///
+    /// # Arguments
+    ///
+    /// * `interfaces` - Interfaces to list
+    /// * `args` - Arguments passed to the command
+    pub fn format_ips(interfaces: &[Interface], args: Option<&str>) -> String {
+        if has_flag(args, "name_only") {
+            let names: Vec<&str> = interfaces.iter().map(|i| i.name.as_str()).collect();
+            return names.join(", ");
+        }
 
-                if arg == "hide_public" {
-                    hide_public = true;
-                }
-            }
+        if has_flag(args, "addr_only") {
+            let addrs: Vec<&str> = interfaces
+                .iter()
+                .flat_map(|i| i.addrs.iter().map(String::as_str))
+                .collect();
+            return addrs.join(", ");
         }
 
-        let interfaces = commands::interfaces(hide_loopback, hide_public, hide_private);
-        for (name, ips) in interfaces.iter() {
-            if ips.len() > 0 {
-                if show_name {
-                    s.push_str(&format!("[{}]", name));
-                }
+        interfaces
+            .iter()
+            .map(|i| format!("{}: {}", i.name, i.addrs.join(", ")))
+            .collect::<Vec<String>>()
+            .join("; ")
+    }
 
-                if show_addr {
-                    if show_name {
-                        s.push_str(": ");
-                    }
+    /// Returns all network interfaces on this computer, sorted by name
+    ///
+    /// # Arguments
+    ///
+    /// * `hide_loopback` - Hides the loopback address
+    /// * `hide_public` - Only show private ips
+    /// * `hide_private` - Hide all private ips
+    pub fn interfaces(
+        &self,
+        hide_loopback: bool,
+        hide_public: bool,
+        hide_private: bool,
+    ) -> Vec<Interface> {
+        let mut interfaces: Vec<Interface> =
+            commands::interfaces(hide_loopback, hide_public, hide_private)
+                .into_iter()
+                .map(|(name, addrs)| Interface { name, addrs })
+                .collect();
+
+        interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+        interfaces
+    }
 
-                    for ip in ips {
-                        s.push_str(&format!("{}", ip));
-                    }
-                } else {
-                    s.push_str(", ")
-                }
-            }
+    /// Returns the number of listening and established connections, or
+    /// zero for both if the sockets could not be queried
+    pub fn connection_counts(&self) -> Connections {
+        let (listening, established) = commands::connections(None).unwrap_or((0, 0));
+        Connections {
+            listening,
+            established,
         }
-
-        s
     }
 
     /// Returns a formatted string listing the number of listening connections
     /// and the number of established connections
-    pub fn connections(&self) -> String {
-        let (listen, established) = commands::connections(None).unwrap_or((0, 0));
-        format!("{} listening, {} established", listen, established)
+    ///
+    /// # Arguments
+    ///
+    /// * `conns` - Number of listening and established connections
+    pub fn format_connections(conns: &Connections) -> String {
+        format!(
+            "{} listening, {} established",
+            conns.listening, conns.established
+        )
     }
 }
+
+/// Returns true if `name` is one of the comma-separated arguments
+///
+/// # Arguments
+///
+/// * `args` - Arguments passed to a command
+/// * `name` - Argument to look for
+fn has_flag(args: Option<&str>, name: &str) -> bool {
+    args.is_some_and(|args| args.split(',').any(|arg| arg.trim() == name))
+}
@@ -1,29 +1,50 @@
 //! All system-related commands (processes, networking, etc)
 
-use crate::commands;
+use crate::commands::{self, LoginRecord};
+use chrono::{offset::Local, DateTime, TimeZone};
+use log::debug;
+use serde::Serialize;
+use std::{cell::OnceCell, collections::BTreeMap};
 
 const SECONDS_PER_WEEK: u64 = 604800;
 const SECONDS_PER_DAY: u64 = 86400;
 const SECONDS_PER_HOUR: u64 = 3600;
 const SECONDS_PER_MINUTE: u64 = 60;
 
-/// Contains commands to retrieve the system state
+/// Number of reboots listed when no count is given
+pub const DEFAULT_REBOOTS: usize = 5;
+
+/// Contains commands to retrieve the system state.  The login accounting
+/// files are each read once, then shared by every fact derived from them
 #[derive(Default)]
-pub struct System;
+pub struct System {
+    /// Open login sessions, from utmp
+    sessions: OnceCell<Vec<Session>>,
+
+    /// Logins, logouts and reboots, from wtmp
+    history: OnceCell<Vec<LoginRecord>>,
+
+    /// Failed login attempts, from btmp, or None if it can't be read
+    failures: OnceCell<Option<Vec<LoginRecord>>>,
+}
 
 impl System {
     pub fn new() -> System {
         System::default()
     }
 
-    /// Returns how long the system has been "up".  Otherwise known as
+    /// Formats how long the system has been "up".  Otherwise known as
     /// time since last reboot or power-on
-    pub fn uptime(&self) -> String {
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds` - Number of seconds since the last reboot
+    pub fn format_uptime(seconds: u64) -> String {
         let mut weeks = 0;
         let mut days = 0;
         let mut hours = 0;
         let mut minutes = 0;
-        let mut seconds = commands::uptime();
+        let mut seconds = seconds;
 
         if seconds > SECONDS_PER_WEEK {
             weeks = seconds / SECONDS_PER_WEEK;
@@ -45,19 +66,90 @@ impl System {
             seconds -= minutes * SECONDS_PER_MINUTE;
         }
 
-        seconds = seconds;
-
         format!(
             "{} weeks, {} days, {} hours, {} minutes, {} seconds",
             weeks, days, hours, minutes, seconds
         )
     }
 
+    /// Returns the number of seconds since the last reboot or power-on
+    pub fn uptime_seconds(&self) -> u64 {
+        commands::uptime()
+    }
+
+    /// Returns every open login session, oldest first
+    pub fn sessions(&self) -> Vec<Session> {
+        self.open_sessions().to_vec()
+    }
+
+    /// Returns every open login session, oldest first, reading utmp the
+    /// first time it is called
+    fn open_sessions(&self) -> &[Session] {
+        self.sessions.get_or_init(|| {
+            let mut sessions: Vec<Session> = commands::sessions()
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|record| {
+                    Some(Session {
+                        login: local_time(record.time)?,
+                        host: remote_host(&record),
+                        user: record.user,
+                        tty: record.line,
+                        pid: record.pid,
+                    })
+                })
+                .collect();
+
+            sessions.sort_by_key(|s| s.login);
+            sessions
+        })
+    }
+
+    /// Returns every login, logout and reboot record, oldest first, reading
+    /// wtmp the first time it is called
+    fn history(&self) -> &[LoginRecord] {
+        self.history
+            .get_or_init(|| commands::login_history().unwrap_or_default())
+    }
+
+    /// Returns every failed login attempt, oldest first, reading btmp the
+    /// first time it is called.  Returns None if btmp can't be read (e.g.,
+    /// without permission)
+    fn failures(&self) -> Option<&[LoginRecord]> {
+        self.failures
+            .get_or_init(|| match commands::failed_logins() {
+                Ok(records) => Some(records),
+                Err(e) => {
+                    debug!("failed to read failed logins: {:?}", e);
+                    None
+                }
+            })
+            .as_deref()
+    }
+
+    /// Returns the names of all users logged in to the system, sorted
+    /// alphabetically
+    ///
+    /// # Arguments
+    ///
+    /// * `sessions` - All open login sessions
+    pub fn user_list(sessions: &[Session]) -> Vec<String> {
+        let mut users: Vec<String> = sessions.iter().map(|s| s.user.clone()).collect();
+        users.sort();
+        users.dedup();
+        users
+    }
+
     /// Formats the string for printing the active users on the system
-    pub fn users(&self) -> String {
+    /// (e.g., `2 users, 3 sessions (alice, bob)`)
+    ///
+    /// # Arguments
+    ///
+    /// * `users` - Names of all logged in users
+    /// * `sessions` - Number of open login sessions
+    pub fn format_users(users: &[String], sessions: usize) -> String {
         let mut usrs = String::new();
 
-        let users = commands::users(None);
         for (i, user) in users.iter().enumerate() {
             usrs.push_str(user);
             if (i + 1) < users.len() {
@@ -65,13 +157,563 @@ impl System {
             }
         }
 
-        format!("{} users ({})", users.len(), usrs)
+        format!("{} users, {} sessions ({})", users.len(), sessions, usrs)
+    }
+
+    /// Formats open login sessions like `who`, one per line
+    ///
+    /// Example:
+    /// alice    pts/0        2026-10-14 09:12 (10.1.2.3)
+    ///
+    /// # Arguments
+    ///
+    /// * `sessions` - Open login sessions to format
+    pub fn format_sessions(sessions: &[Session]) -> String {
+        sessions
+            .iter()
+            .map(|s| {
+                let line = format!(
+                    "{:<8} {:<12} {}",
+                    s.user,
+                    s.tty,
+                    s.login.format("%Y-%m-%d %H:%M")
+                );
+                if s.host.is_empty() {
+                    line
+                } else {
+                    format!("{} ({})", line, s.host)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Returns a user's last login before the current session, from wtmp or,
+    /// if wtmp has been rotated since, lastlog.  The current session is the
+    /// user's newest open session, if they have one
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - Name of the user
+    pub fn last_login(&self, user: &str) -> Option<LastLogin> {
+        let current = self
+            .open_sessions()
+            .iter()
+            .filter(|s| s.user == user)
+            .map(|s| s.login.timestamp())
+            .max()
+            .unwrap_or(i64::MAX);
+
+        let record = self
+            .history()
+            .iter()
+            .filter(|r| r.kind == libc::USER_PROCESS && r.user == user && r.time < current)
+            .max_by_key(|r| r.time)
+            .cloned()
+            .or_else(|| {
+                let account = commands::account_by_name(user).ok()??;
+                let record = commands::last_login(account.uid).ok()??;
+                Some(record).filter(|r| r.time < current)
+            })?;
+
+        Some(LastLogin {
+            time: local_time(record.time)?,
+            host: remote_host(&record),
+            tty: record.line,
+        })
+    }
+
+    /// Formats a last login (e.g., `Tue Oct 14 09:12 from 10.1.2.3`), naming
+    /// the terminal instead for local logins
+    ///
+    /// # Arguments
+    ///
+    /// * `login` - Last login to format
+    pub fn format_last_login(login: &LastLogin) -> String {
+        let time = login.time.format("%a %b %e %H:%M");
+        if login.host.is_empty() {
+            format!("{} on {}", time, login.tty)
+        } else {
+            format!("{} from {}", time, login.host)
+        }
+    }
+
+    /// Returns the failed attempts to log in as a user since their last
+    /// login, from btmp, or None if btmp can't be read (e.g., without
+    /// permission)
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - Name of the user
+    pub fn failed_logins(&self, user: &str) -> Option<FailedLogins> {
+        let records = self.failures()?;
+        let since = self.last_login(user).map(|login| login.time);
+        let attempts: Vec<_> = records
+            .iter()
+            .filter(|r| r.user == user && since.is_none_or(|t| r.time > t.timestamp()))
+            .collect();
+
+        let last = attempts.iter().max_by_key(|r| r.time).and_then(|r| {
+            Some(FailedLogin {
+                time: local_time(r.time)?,
+                tty: r.line.clone(),
+                host: remote_host(r),
+            })
+        });
+
+        Some(FailedLogins {
+            count: attempts.len(),
+            since,
+            last,
+        })
+    }
+
+    /// Formats failed logins as a warning (e.g., `7 failed login attempts
+    /// since your last login, most recent from 203.0.113.9`), or nothing if
+    /// there weren't any
+    ///
+    /// # Arguments
+    ///
+    /// * `failed` - Failed logins to format
+    pub fn format_failed_logins(failed: &FailedLogins) -> String {
+        if failed.count == 0 {
+            return String::new();
+        }
+
+        let mut s = format!(
+            "{} failed login {} since your last login",
+            failed.count,
+            if failed.count == 1 {
+                "attempt"
+            } else {
+                "attempts"
+            }
+        );
+
+        if let Some(last) = &failed.last {
+            if last.host.is_empty() {
+                s.push_str(&format!(", most recent on {}", last.tty));
+            } else {
+                s.push_str(&format!(", most recent from {}", last.host));
+            }
+        }
+        s
+    }
+
+    /// Returns the most recent reboots recorded in wtmp, newest first
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - Maximum number of reboots to return
+    pub fn reboots(&self, count: usize) -> Vec<Reboot> {
+        let mut reboots: Vec<Reboot> = self
+            .history()
+            .iter()
+            .filter(|r| r.kind == libc::BOOT_TIME)
+            .filter_map(|r| {
+                Some(Reboot {
+                    time: local_time(r.time)?,
+                    kernel: r.host.clone(),
+                })
+            })
+            .collect();
+
+        reboots.sort_by_key(|r| std::cmp::Reverse(r.time));
+        reboots.truncate(count);
+        reboots
+    }
+
+    /// Formats reboots, one per line (e.g., `Tue Oct 14 09:12 (6.1.0-18-amd64)`)
+    ///
+    /// # Arguments
+    ///
+    /// * `reboots` - Reboots to format
+    pub fn format_reboots(reboots: &[Reboot]) -> String {
+        reboots
+            .iter()
+            .map(|r| {
+                let time = r.time.format("%a %b %e %H:%M");
+                if r.kernel.is_empty() {
+                    time.to_string()
+                } else {
+                    format!("{} ({})", time, r.kernel)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Returns how many times each user has logged in since wtmp was last
+    /// rotated
+    pub fn login_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for record in self.history() {
+            if record.kind == libc::USER_PROCESS && !record.user.is_empty() {
+                *counts.entry(record.user.clone()).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// Formats the login count of each user (e.g., `alice: 12, bob: 3`)
+    ///
+    /// # Arguments
+    ///
+    /// * `counts` - Number of logins of each user
+    pub fn format_login_counts(counts: &BTreeMap<String, usize>) -> String {
+        counts
+            .iter()
+            .map(|(user, count)| format!("{}: {}", user, count))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// Returns the ID of this machine, or None if it doesn't have one
+    pub fn machine_id(&self) -> Option<String> {
+        commands::machine_id().ok().filter(|id| !id.is_empty())
+    }
+
+    /// Returns the ID of the current boot, or None if it can't be read
+    pub fn boot_id(&self) -> Option<String> {
+        commands::boot_id().ok().filter(|id| !id.is_empty())
+    }
+
+    /// Returns the number of processes running on the system
+    pub fn process_count(&self) -> usize {
+        commands::process_count().unwrap_or(0)
+    }
+
+    /// Formats the count of all processes running on the system
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - Number of running processes
+    pub fn format_processes(count: usize) -> String {
+        format!("{} running", count)
+    }
+
+    /// Returns usage of the filesystem containing `path`, or None if the
+    /// filesystem could not be queried
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Any path on the filesystem (e.g., a mount point)
+    pub fn disk(&self, path: &str) -> Option<Disk> {
+        let (total, free, available) = commands::disk_usage(path).ok()?;
+        let used = total.saturating_sub(free);
+
+        // Like df, the percentage excludes blocks reserved for root
+        let used_pct = if used + available > 0 {
+            used as f64 * 100.0 / (used + available) as f64
+        } else {
+            0.0
+        };
+
+        Some(Disk {
+            path: path.to_string(),
+            total,
+            used,
+            available,
+            used_pct,
+        })
+    }
+
+    /// Returns usage of physical memory and swap, or None if they could
+    /// not be queried
+    pub fn memory(&self) -> Option<(Memory, Memory)> {
+        let (total, available, swap_total, swap_free) = commands::memory().ok()?;
+        Some((
+            Memory::new(total, available),
+            Memory::new(swap_total, swap_free),
+        ))
+    }
+
+    /// Formats the usage of memory or swap (e.g., `3.1 GiB of 7.7 GiB (40%)`)
+    ///
+    /// # Arguments
+    ///
+    /// * `mem` - Memory usage to format
+    pub fn format_memory(mem: &Memory) -> String {
+        format!(
+            "{} of {} ({:.0}%)",
+            format_bytes(mem.used),
+            format_bytes(mem.total),
+            mem.used_pct
+        )
+    }
+
+    /// Returns how busy all CPUs are, or None if it could not be measured.
+    /// Measuring takes a short moment, so this is only done on request
+    pub fn cpu(&self) -> Option<Cpu> {
+        let used_pct = commands::cpu_usage().ok()?;
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+        Some(Cpu { cores, used_pct })
+    }
+
+    /// Formats the usage of all CPUs (e.g., `12% of 4 cores`)
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - CPU usage to format
+    pub fn format_cpu(cpu: &Cpu) -> String {
+        format!("{:.0}% of {} cores", cpu.used_pct, cpu.cores)
+    }
+
+    /// Returns the identification of the operating system from
+    /// os-release, or None if it has no os-release file
+    pub fn os(&self) -> Option<OsRelease> {
+        let mut vars = commands::os_release().ok()?;
+        let mut take = |key: &str| vars.remove(key).unwrap_or_default();
+
+        let id = take("ID");
+        let name = take("NAME");
+        let pretty_name = take("PRETTY_NAME");
+        let version_id = take("VERSION_ID");
+        let id_like = take("ID_LIKE")
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+
+        Some(OsRelease {
+            id: if id.is_empty() {
+                "linux".to_string()
+            } else {
+                id
+            },
+            id_like,
+            name,
+            pretty_name,
+            version_id,
+        })
+    }
+
+    /// Formats the name of the operating system, preferring its pretty
+    /// name (e.g., `Debian GNU/Linux 12 (bookworm)`)
+    ///
+    /// # Arguments
+    ///
+    /// * `os` - Identification of the operating system
+    pub fn format_os(os: &OsRelease) -> String {
+        if !os.pretty_name.is_empty() {
+            os.pretty_name.clone()
+        } else {
+            format!("{} {}", os.name, os.version_id).trim().to_string()
+        }
+    }
+
+    /// Formats the usage of a filesystem (e.g., `4.2 GiB of 20.0 GiB (22%)`)
+    ///
+    /// # Arguments
+    ///
+    /// * `disk` - Filesystem usage to format
+    pub fn format_disk(disk: &Disk) -> String {
+        format!(
+            "{} of {} ({:.0}%)",
+            format_bytes(disk.used),
+            format_bytes(disk.total),
+            disk.used_pct
+        )
     }
+}
+
+/// A user's open login session, from utmp
+#[derive(Clone, Debug, Serialize)]
+pub struct Session {
+    /// Name of the user logged in
+    pub user: String,
+
+    /// Terminal the session is on (e.g., `pts/0`)
+    pub tty: String,
+
+    /// Remote host the user logged in from, empty for local logins
+    pub host: String,
+
+    /// Time the user logged in
+    pub login: DateTime<Local>,
+
+    /// Process ID of the session's login process
+    pub pid: i32,
+}
+
+/// A user's previous login, from wtmp or lastlog
+#[derive(Clone, Debug, Serialize)]
+pub struct LastLogin {
+    /// Time the user logged in
+    pub time: DateTime<Local>,
+
+    /// Terminal the user logged in on (e.g., `pts/0`)
+    pub tty: String,
+
+    /// Remote host the user logged in from, empty for local logins
+    pub host: String,
+}
+
+/// Failed attempts to log in as a user since their last login, from btmp
+#[derive(Clone, Debug, Serialize)]
+pub struct FailedLogins {
+    /// Number of failed attempts
+    pub count: usize,
+
+    /// Time of the last login, or None if all attempts were counted
+    pub since: Option<DateTime<Local>>,
+
+    /// Most recent failed attempt
+    pub last: Option<FailedLogin>,
+}
+
+/// A failed attempt to log in
+#[derive(Clone, Debug, Serialize)]
+pub struct FailedLogin {
+    /// Time of the attempt
+    pub time: DateTime<Local>,
+
+    /// Terminal the attempt was made on (e.g., `ssh:notty`)
+    pub tty: String,
+
+    /// Remote host the attempt came from, empty for local attempts
+    pub host: String,
+}
+
+/// A reboot, from wtmp
+#[derive(Clone, Debug, Serialize)]
+pub struct Reboot {
+    /// Time the system booted
+    pub time: DateTime<Local>,
 
-    /// Returns the count of all processes running on the system
-    pub fn processes(&self) -> String {
-        let procs = commands::processes(None);
+    /// Version of the kernel booted, if recorded
+    pub kernel: String,
+}
+
+/// Usage of a single filesystem
+#[derive(Clone, Debug, Serialize)]
+pub struct Disk {
+    /// Path the usage was queried for
+    pub path: String,
+
+    /// Size of the filesystem, in bytes
+    pub total: u64,
+
+    /// Bytes in use
+    pub used: u64,
+
+    /// Bytes available to unprivileged users
+    pub available: u64,
+
+    /// Percentage of the usable space in use
+    pub used_pct: f64,
+}
+
+/// Identification of the operating system, from os-release
+#[derive(Clone, Debug, Serialize)]
+pub struct OsRelease {
+    /// Lowercase identifier of the distribution (e.g., `debian`)
+    pub id: String,
+
+    /// Identifiers of the distributions this one is derived from, closest
+    /// first (e.g., `["ubuntu", "debian"]` for Linux Mint)
+    pub id_like: Vec<String>,
+
+    /// Name of the distribution, without a version
+    pub name: String,
+
+    /// Name of the distribution for display, usually with a version
+    pub pretty_name: String,
+
+    /// Version of the distribution (e.g., `12`)
+    pub version_id: String,
+}
+
+/// Usage of physical memory or swap
+#[derive(Clone, Debug, Serialize)]
+pub struct Memory {
+    /// Size of the memory, in bytes
+    pub total: u64,
+
+    /// Bytes in use
+    pub used: u64,
+
+    /// Bytes available without swapping (for swap, bytes free)
+    pub available: u64,
+
+    /// Percentage of the memory in use
+    pub used_pct: f64,
+}
+
+impl Memory {
+    /// Calculates usage from the total and available number of bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `total` - Size of the memory, in bytes
+    /// * `available` - Bytes that are not in use
+    fn new(total: u64, available: u64) -> Memory {
+        let used = total.saturating_sub(available);
+        let used_pct = if total > 0 {
+            used as f64 * 100.0 / total as f64
+        } else {
+            0.0
+        };
+
+        Memory {
+            total,
+            used,
+            available,
+            used_pct,
+        }
+    }
+}
+
+/// Usage of all CPUs
+#[derive(Clone, Debug, Serialize)]
+pub struct Cpu {
+    /// Number of logical cores
+    pub cores: usize,
+
+    /// Percentage of time all cores were busy
+    pub used_pct: f64,
+}
+
+/// Converts a UNIX timestamp to local time, or None if it is out of range
+///
+/// # Arguments
+///
+/// * `timestamp` - Seconds since the UNIX epoch
+fn local_time(timestamp: i64) -> Option<DateTime<Local>> {
+    Local.timestamp_opt(timestamp, 0).single()
+}
+
+/// Returns the remote host of a login record, falling back to its address
+/// when the host name wasn't recorded
+///
+/// # Arguments
+///
+/// * `record` - Login record to read
+fn remote_host(record: &LoginRecord) -> String {
+    match record.addr {
+        Some(addr) if record.host.is_empty() => addr.to_string(),
+        _ => record.host.clone(),
+    }
+}
+
+/// Formats a number of bytes using binary units (e.g., `1.5 GiB`)
+///
+/// # Arguments
+///
+/// * `bytes` - Number of bytes to format
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
 
-        format!("{} running", procs.len() - 1)
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
     }
 }
@@ -1,6 +1,12 @@
 //! All information regarding the user that logged in, either via tty, console, etc.
 
 use crate::commands;
+use chrono::{offset::Local, DateTime, TimeZone};
+use serde::Serialize;
+use std::{env, net::IpAddr};
+
+/// Prefixes of terminals attached to a serial line
+const SERIAL_TTYS: [&str; 5] = ["ttyS", "ttyUSB", "ttyACM", "ttyAMA", "hvc"];
 
 /// Represents the logged in user.  Contains information like the username,
 /// tty or where the user is logged in, etc.
@@ -9,8 +15,69 @@ pub struct User {
     /// User's username
     pub name: String,
 
-    /// User's tty
+    /// User ID
+    pub uid: u32,
+
+    /// ID of the user's primary group
+    pub gid: u32,
+
+    /// User's full name, from the passwd database's GECOS field
+    pub full_name: String,
+
+    /// User's home directory
+    pub home: String,
+
+    /// User's login shell
+    pub shell: String,
+
+    /// Names of every group the user belongs to, primary group first
+    pub groups: Vec<String>,
+
+    /// User's tty, without `/dev/` (e.g., `pts/0`)
     pub tty: String,
+
+    /// How the user logged in
+    pub method: LoginMethod,
+
+    /// Where the user logged in from, for remote logins
+    pub remote: Option<Remote>,
+
+    /// Time the user's session started, if it is in utmp
+    pub login: Option<DateTime<Local>>,
+}
+
+/// How a user logged in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoginMethod {
+    /// A virtual console on the machine itself (e.g., `tty1`)
+    Console,
+
+    /// Over SSH
+    Ssh,
+
+    /// Over mosh, which starts its session over SSH
+    Mosh,
+
+    /// A serial line (e.g., `ttyS0`)
+    Serial,
+
+    /// A terminal emulator on the machine itself
+    Terminal,
+
+    /// Not on a terminal (e.g., from cron)
+    #[default]
+    Unknown,
+}
+
+/// Address of the client a user logged in from
+#[derive(Clone, Debug, Serialize)]
+pub struct Remote {
+    /// IP address, or host name if only that is known
+    pub address: String,
+
+    /// Port on the client, if known
+    pub port: Option<u16>,
 }
 
 impl User {
@@ -18,10 +85,183 @@ impl User {
     pub fn new() -> User {
         let mut user = User::default();
 
-        if let Ok(name) = commands::user(None) {
-            user.name = name;
+        if let Ok(account) = commands::user() {
+            user.groups = commands::groups(&account.name, account.gid)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(_, name)| name)
+                .collect();
+            user.full_name = full_name(&account.gecos, &account.name);
+            user.name = account.name;
+            user.uid = account.uid;
+            user.gid = account.gid;
+            user.home = account.home;
+            user.shell = account.shell;
         }
 
+        user.tty = commands::tty().unwrap_or_default();
+
+        // The session on this terminal, if there is one, says when it
+        // started and where from even if the environment was cleared
+        let session = commands::sessions()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|s| !user.tty.is_empty() && s.line == user.tty)
+            .max_by_key(|s| s.time);
+        let host = session.as_ref().map_or("", |s| s.host.as_str());
+
+        user.login = session
+            .as_ref()
+            .and_then(|s| Local.timestamp_opt(s.time, 0).single());
+
+        // mosh-server records itself in utmp as "mosh [pid]", after the
+        // client's address if it knows it
+        let mosh = host.starts_with("mosh [") || host.contains(" via mosh [");
+        user.remote = ssh_client().or_else(|| {
+            remote_host(host).map(|address| Remote {
+                address,
+                port: None,
+            })
+        });
+
+        user.method = if mosh {
+            LoginMethod::Mosh
+        } else if user.remote.is_some() {
+            LoginMethod::Ssh
+        } else if SERIAL_TTYS.iter().any(|p| user.tty.starts_with(p)) {
+            LoginMethod::Serial
+        } else if user.tty == "console" || user.tty.starts_with("tty") {
+            LoginMethod::Console
+        } else if user.tty.is_empty() {
+            LoginMethod::Unknown
+        } else {
+            LoginMethod::Terminal
+        };
+
         user
     }
+
+    /// Formats where the user logged in from (e.g., `203.0.113.5:51234`)
+    ///
+    /// # Arguments
+    ///
+    /// * `remote` - Address of the client
+    pub fn format_remote(remote: &Remote) -> String {
+        match (remote.port, remote.address.contains(':')) {
+            (Some(port), true) => format!("[{}]:{}", remote.address, port),
+            (Some(port), false) => format!("{}:{}", remote.address, port),
+            (None, _) => remote.address.clone(),
+        }
+    }
+}
+
+impl LoginMethod {
+    /// Returns the name of the login method, as it is serialized
+    pub fn name(self) -> &'static str {
+        match self {
+            LoginMethod::Console => "console",
+            LoginMethod::Ssh => "ssh",
+            LoginMethod::Mosh => "mosh",
+            LoginMethod::Serial => "serial",
+            LoginMethod::Terminal => "terminal",
+            LoginMethod::Unknown => "unknown",
+        }
+    }
+}
+
+/// Extracts the full name from a GECOS field, the first of its
+/// comma-separated entries.  As on BSD, an `&` stands for the capitalized
+/// username
+///
+/// # Arguments
+///
+/// * `gecos` - GECOS field of the passwd entry
+/// * `name` - Name of the user
+fn full_name(gecos: &str, name: &str) -> String {
+    let mut chars = name.chars();
+    let capitalized: String = chars
+        .next()
+        .map(|c| c.to_uppercase().chain(chars).collect())
+        .unwrap_or_default();
+
+    gecos
+        .split(',')
+        .next()
+        .unwrap_or("")
+        .replace('&', &capitalized)
+        .trim()
+        .to_string()
+}
+
+/// Returns the remote host from a utmp session's host field, or None if
+/// the field names something local instead.  Terminal multiplexers and X
+/// record markers there, such as `tmux(1234).%0`, `host:S.0` (screen) or
+/// `:0.0`, so only IP addresses and valid host names are accepted
+///
+/// # Arguments
+///
+/// * `host` - Host field of the session's utmp record
+fn remote_host(host: &str) -> Option<String> {
+    let host = host.split(" via mosh [").next().unwrap_or("").trim();
+    let is_hostname = !host.starts_with(['-', '.'].as_ref())
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+
+    if !host.is_empty() && (host.parse::<IpAddr>().is_ok() || is_hostname) {
+        Some(host.to_string())
+    } else {
+        None
+    }
+}
+
+/// Reads the SSH client's address and port from `SSH_CONNECTION` (client
+/// address, client port, server address, server port) or, from older
+/// servers, `SSH_CLIENT` (client address, client port, server port)
+fn ssh_client() -> Option<Remote> {
+    let var = env::var("SSH_CONNECTION")
+        .or_else(|_| env::var("SSH_CLIENT"))
+        .ok()?;
+    let mut fields = var.split_whitespace();
+
+    Some(Remote {
+        address: fields.next()?.to_string(),
+        port: fields.next().and_then(|p| p.parse().ok()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_remote_hosts() {
+        assert_eq!(remote_host("203.0.113.5").as_deref(), Some("203.0.113.5"));
+        assert_eq!(remote_host("2001:db8::1").as_deref(), Some("2001:db8::1"));
+        assert_eq!(
+            remote_host("laptop.example.com").as_deref(),
+            Some("laptop.example.com")
+        );
+        assert_eq!(
+            remote_host("203.0.113.5 via mosh [1234]").as_deref(),
+            Some("203.0.113.5")
+        );
+    }
+
+    #[test]
+    fn rejects_local_markers() {
+        assert_eq!(remote_host(""), None);
+        assert_eq!(remote_host(":0"), None);
+        assert_eq!(remote_host(":0.0"), None);
+        assert_eq!(remote_host("tmux(1234).%0"), None);
+        assert_eq!(remote_host("laptop:S.0"), None);
+        assert_eq!(remote_host("mosh [1234]"), None);
+    }
+
+    #[test]
+    fn reads_full_names() {
+        assert_eq!(full_name("Alice Smith,,,", "alice"), "Alice Smith");
+        assert_eq!(full_name("& Jones,Room 1", "bob"), "Bob Jones");
+        assert_eq!(full_name("", "carol"), "");
+    }
 }
@@ -0,0 +1,285 @@
+//! Boxes drawn around blocks of a template.
+//!
+//! ```text
+//! {% panel title="Network" style=rounded %}
+//! IPs: {{ ipaddr(addr_only) }}
+//! Connections: {{ conns }}
+//! {% endpanel %}
+//! ```
+//!
+//! The box is sized to the widest line of its content, measured without
+//! escape sequences, so styled text lines up.  The border and title are
+//! styled with the theme's `border` and `title` roles, if it has them.
+
+use crate::{
+    ansi::{self, Style},
+    template,
+    theme::Theme,
+};
+use log::error;
+
+/// Characters a box is drawn with
+#[derive(Clone, Copy, Debug)]
+pub struct Border {
+    pub horizontal: char,
+    pub vertical: char,
+    pub top_left: char,
+    pub top_right: char,
+    pub bottom_left: char,
+    pub bottom_right: char,
+}
+
+impl Border {
+    /// Plain ASCII, for terminals without Unicode
+    pub const ASCII: Border = Border {
+        horizontal: '-',
+        vertical: '|',
+        top_left: '+',
+        top_right: '+',
+        bottom_left: '+',
+        bottom_right: '+',
+    };
+
+    /// Single lines with square corners
+    pub const SINGLE: Border = Border {
+        horizontal: '─',
+        vertical: '│',
+        top_left: '┌',
+        top_right: '┐',
+        bottom_left: '└',
+        bottom_right: '┘',
+    };
+
+    /// Single lines with rounded corners
+    pub const ROUNDED: Border = Border {
+        horizontal: '─',
+        vertical: '│',
+        top_left: '╭',
+        top_right: '╮',
+        bottom_left: '╰',
+        bottom_right: '╯',
+    };
+
+    /// Double lines
+    pub const DOUBLE: Border = Border {
+        horizontal: '═',
+        vertical: '║',
+        top_left: '╔',
+        top_right: '╗',
+        bottom_left: '╚',
+        bottom_right: '╝',
+    };
+
+    /// Heavy (thick) lines
+    pub const HEAVY: Border = Border {
+        horizontal: '━',
+        vertical: '┃',
+        top_left: '┏',
+        top_right: '┓',
+        bottom_left: '┗',
+        bottom_right: '┛',
+    };
+
+    /// Returns the border with the given name (ascii, single, rounded,
+    /// double or heavy), or None if there isn't one
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the border
+    pub fn from_name(name: &str) -> Option<Border> {
+        match name {
+            "ascii" => Some(Border::ASCII),
+            "single" => Some(Border::SINGLE),
+            "rounded" => Some(Border::ROUNDED),
+            "double" => Some(Border::DOUBLE),
+            "heavy" => Some(Border::HEAVY),
+            _ => None,
+        }
+    }
+}
+
+/// A box to draw around a block
+#[derive(Clone, Debug)]
+pub struct Panel {
+    /// Text shown in the top border, if any
+    pub title: Option<String>,
+
+    /// Characters to draw the box with
+    pub border: Border,
+
+    /// Minimum number of columns inside the box
+    pub width: usize,
+
+    /// Style of the border
+    pub border_style: Style,
+
+    /// Style of the title
+    pub title_style: Style,
+}
+
+impl Panel {
+    /// Parses the arguments of a panel block, such as
+    /// `title="Network" style=double width=40`.  Unknown border styles fall
+    /// back to single lines
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Whitespace separated arguments of the block.  Values
+    ///   containing spaces must be quoted
+    /// * `theme` - Theme to style the border and title with
+    pub fn parse(args: &str, theme: &Theme) -> Panel {
+        let mut panel = Panel {
+            title: None,
+            border: Border::SINGLE,
+            width: 0,
+            border_style: theme.resolve("border"),
+            title_style: theme.resolve("title"),
+        };
+
+        for arg in template::split_top_level(args, ' ') {
+            let (key, value) = match arg.find('=') {
+                Some(i) => (&arg[..i], ansi::unquote(&arg[i + 1..])),
+                None => (arg, ""),
+            };
+
+            match key {
+                "" => {}
+                "title" => panel.title = Some(value.to_string()),
+                "style" => panel.border = Border::from_name(value).unwrap_or(Border::SINGLE),
+                "width" => panel.width = value.parse().unwrap_or(0),
+                key => error!("unknown panel argument {}", key),
+            }
+        }
+
+        panel
+    }
+
+    /// Draws the box around rendered text.  A trailing newline is kept
+    /// after the box
+    ///
+    /// # Arguments
+    ///
+    /// * `body` - Rendered content of the panel
+    pub fn draw(&self, body: &str) -> String {
+        let (content, newline) = match body.strip_suffix('\n') {
+            Some(content) => (content, "\n"),
+            None => (body, ""),
+        };
+
+        let lines: Vec<&str> = content.split('\n').collect();
+        let title = self.title.as_deref().map(|t| format!(" {} ", t));
+        let title_width = title.as_deref().map_or(0, ansi::width);
+
+        let width = lines
+            .iter()
+            .map(|line| ansi::width(line))
+            .max()
+            .unwrap_or(0)
+            .max(self.width)
+            .max(title_width.saturating_sub(1));
+
+        let b = &self.border;
+        let h = b.horizontal.to_string();
+        let border = |s: String| ansi::paint(&s, &self.border_style);
+
+        // The title sits one column in from the corner: ╭─ Title ───╮
+        let mut s = String::new();
+        match &title {
+            Some(title) => {
+                s.push_str(&border(format!("{}{}", b.top_left, h)));
+                s.push_str(&ansi::paint(title, &self.title_style));
+                s.push_str(&border(format!(
+                    "{}{}",
+                    h.repeat(width + 1 - title_width),
+                    b.top_right
+                )));
+            }
+            None => s.push_str(&border(format!(
+                "{}{}{}",
+                b.top_left,
+                h.repeat(width + 2),
+                b.top_right
+            ))),
+        }
+        s.push('\n');
+
+        let vertical = border(b.vertical.to_string());
+        for line in lines {
+            s.push_str(&format!(
+                "{} {} {}\n",
+                vertical,
+                ansi::pad(line, width, false),
+                vertical
+            ));
+        }
+
+        s.push_str(&border(format!(
+            "{}{}{}",
+            b.bottom_left,
+            h.repeat(width + 2),
+            b.bottom_right
+        )));
+        s.push_str(newline);
+
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn panel(args: &str) -> Panel {
+        Panel::parse(args, &Theme::default())
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let p = panel(r#"title="Disk usage" style=double width=40 shadow"#);
+        assert_eq!(p.title.as_deref(), Some("Disk usage"));
+        assert_eq!(p.border.top_left, '╔');
+        assert_eq!(p.width, 40);
+
+        let p = panel("style=wavy width=wide");
+        assert!(p.title.is_none());
+        assert_eq!(p.border.top_left, '┌');
+        assert_eq!(p.width, 0);
+    }
+
+    #[test]
+    fn sizes_box_to_content() {
+        assert_eq!(
+            panel("style=ascii").draw("ab\n\x1b[1mcde\x1b[0m\n"),
+            "+-----+\n| ab  |\n| \x1b[1mcde\x1b[0m |\n+-----+\n"
+        );
+        assert_eq!(
+            panel("style=rounded width=3").draw("a"),
+            "╭─────╮\n│ a   │\n╰─────╯"
+        );
+    }
+
+    #[test]
+    fn widens_box_to_fit_title() {
+        assert_eq!(
+            panel("title=Network").draw("ab"),
+            "┌─ Network ┐\n│ ab       │\n└──────────┘"
+        );
+        assert_eq!(
+            panel("title=Net").draw("abcdef"),
+            "┌─ Net ──┐\n│ abcdef │\n└────────┘"
+        );
+    }
+
+    #[test]
+    fn styles_border_and_title() {
+        let theme: Theme =
+            toml::from_str("[roles]\nborder = \"fg=blue\"\ntitle = \"bold\"").unwrap();
+        let p = Panel::parse("title=A style=ascii", &theme);
+        assert_eq!(
+            p.draw("a"),
+            "\x1b[34m+-\x1b[0m\x1b[1m A \x1b[0m\x1b[34m+\x1b[0m\n\
+             \x1b[34m|\x1b[0m a  \x1b[34m|\x1b[0m\n\
+             \x1b[34m+----+\x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Executable plugins that provide additional commands to templates.
+//!
+//! A plugin is any executable file in the plugin directory.  When run with
+//! `--describe`, a plugin must print a JSON object listing the commands it
+//! provides:
+//!
+//! ```json
+//! { "name": "backups", "commands": ["lastbackup", "backupsize"] }
+//! ```
+//!
+//! When a template uses one of those commands (e.g., `{{ lastbackup(daily) }}`)
+//! the plugin is run with no arguments and a JSON request is written to its
+//! stdin:
+//!
+//! ```json
+//! { "command": "lastbackup", "args": ["daily"] }
+//! ```
+//!
+//! The plugin must print a single JSON value (string, number, bool, array,
+//! object or null) to stdout and exit successfully.
+
+use crate::{
+    ansi,
+    error::{Error, MotdResult},
+    template,
+};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Read, Write},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Default directory plugins are loaded from
+pub const PLUGIN_DIR: &str = "/usr/lib/motd-rs/plugins";
+
+/// How long a plugin may run before it is killed
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Response to the `--describe` handshake
+#[derive(Debug, Deserialize)]
+struct Description {
+    /// Name of the plugin, defaults to the file name
+    name: Option<String>,
+
+    /// Commands this plugin provides
+    commands: Vec<String>,
+}
+
+/// Request sent to a plugin on stdin
+#[derive(Debug, Serialize)]
+struct Request<'a> {
+    /// Command to run
+    command: &'a str,
+
+    /// Comma-separated arguments passed to the command in the template
+    args: Vec<&'a str>,
+}
+
+/// An executable that provides commands to templates
+#[derive(Clone, Debug)]
+pub struct Plugin {
+    /// Name of the plugin
+    pub name: String,
+
+    /// Path to the executable
+    pub path: PathBuf,
+}
+
+impl Plugin {
+    /// Runs the plugin with the given arguments, writing `input` to its stdin
+    /// and returning everything it printed to stdout.  Fails if the plugin
+    /// exits unsuccessfully or runs longer than the timeout
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Command line arguments to pass to the plugin
+    /// * `input` - Data to write to the plugin's stdin
+    fn run(&self, args: &[&str], input: &[u8]) -> MotdResult<String> {
+        let mut child = Command::new(&self.path)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // A plugin is free to ignore its input (e.g., for --describe)
+            let _ = stdin.write_all(input);
+        }
+
+        // Read stdout on another thread so a plugin with a lot of output
+        // can't block on a full pipe while we wait for it to exit
+        let mut stdout = child.stdout.take().ok_or(Error::CommandFailed)?;
+        let reader = thread::spawn(move || {
+            let mut output = String::new();
+            stdout.read_to_string(&mut output).map(|_| output)
+        });
+
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+
+            if start.elapsed() > TIMEOUT {
+                warn!("plugin {} timed out", self.name);
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(Error::CommandFailed);
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        let output = reader.join().map_err(|_| Error::CommandFailed)??;
+        if !status.success() {
+            return Err(Error::CommandFailed);
+        }
+
+        Ok(output)
+    }
+
+    /// Performs the `--describe` handshake, returning the commands this
+    /// plugin provides
+    fn describe(&self) -> MotdResult<Description> {
+        let output = self.run(&["--describe"], &[])?;
+        Ok(serde_json::from_str(&output)?)
+    }
+
+    /// Runs a command provided by this plugin, returning the value it produced
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - Name of the command to run
+    /// * `args` - Comma-separated list of arguments from the template
+    pub fn call(&self, command: &str, args: Option<&str>) -> MotdResult<Value> {
+        let req = Request {
+            command,
+            args: args
+                .map(|a| {
+                    template::split_top_level(a, ',')
+                        .into_iter()
+                        .map(|arg| ansi::unquote(arg.trim()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        };
+
+        let output = self.run(&[], &serde_json::to_vec(&req)?)?;
+        Ok(serde_json::from_str(&output)?)
+    }
+}
+
+/// All plugins found in the plugin directory, indexed by the commands
+/// they provide
+#[derive(Debug, Default)]
+pub struct Plugins {
+    commands: HashMap<String, Plugin>,
+}
+
+impl Plugins {
+    /// Finds all executables in a directory and asks each for the commands
+    /// it provides.  Plugins that fail the handshake are skipped.  If two
+    /// plugins provide the same command, the first (by file name) wins
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Directory to search for plugins
+    pub fn discover<P: AsRef<Path>>(dir: P) -> Plugins {
+        let mut plugins = Plugins::default();
+
+        let mut paths: Vec<PathBuf> = match fs::read_dir(dir.as_ref()) {
+            Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+            Err(e) => {
+                debug!("no plugins loaded from {:?}: {}", dir.as_ref(), e);
+                return plugins;
+            }
+        };
+        paths.sort();
+
+        for path in paths {
+            let executable = fs::metadata(&path)
+                .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false);
+
+            if !executable {
+                continue;
+            }
+
+            let mut plugin = Plugin {
+                name: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                path,
+            };
+
+            let desc = match plugin.describe() {
+                Ok(desc) => desc,
+                Err(e) => {
+                    warn!("plugin {} failed to describe itself: {:?}", plugin.name, e);
+                    continue;
+                }
+            };
+
+            if let Some(name) = desc.name {
+                plugin.name = name;
+            }
+
+            for command in desc.commands {
+                debug!("plugin {} provides {}", plugin.name, command);
+                plugins
+                    .commands
+                    .entry(command)
+                    .or_insert_with(|| plugin.clone());
+            }
+        }
+
+        plugins
+    }
+
+    /// Runs a command provided by a plugin.  Returns None if no plugin
+    /// provides the command
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - Name of the command to run
+    /// * `args` - Comma-separated list of arguments from the template
+    pub fn call(&self, command: &str, args: Option<&str>) -> Option<MotdResult<Value>> {
+        self.commands
+            .get(command)
+            .map(|plugin| plugin.call(command, args))
+    }
+}
@@ -0,0 +1,158 @@
+//! Sandboxed scripting for custom facts and formatting.
+//!
+//! Functions defined in a `{% script %}...{% endscript %}` block of a
+//! template, or in a script file listed in the configuration, can be used
+//! in a template just like a built-in command:
+//!
+//! ```text
+//! {% script %}
+//! fn connwarn() {
+//!     let facts = facts();
+//!     if facts.connections.established > 500 { "WARNING: busy" } else { "" }
+//! }
+//! {% endscript %}
+//! Connections.......: {{ conns }} {{ connwarn }}
+//! ```
+//!
+//! Built-in facts are available through the `facts()` function, which returns
+//! the same data printed by `motd facts`.  Each call is limited in the time
+//! it may run, the number of operations it may perform and the size of the
+//! strings, arrays and maps it may build.
+
+use crate::{
+    ansi,
+    config::ScriptConfig,
+    error::{Error, MotdResult},
+    motd::Facts,
+    template,
+};
+use log::{debug, error};
+use rhai::{module_resolvers::DummyModuleResolver, CallFnOptions, Dynamic, Engine, Scope, AST};
+use serde_json::Value;
+use std::{
+    cell::Cell,
+    fs,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// A scripting engine and all functions loaded into it
+pub struct Scripts {
+    engine: Engine,
+    ast: AST,
+
+    /// Time the current function call started, used to enforce the timeout
+    started: Rc<Cell<Instant>>,
+}
+
+impl Scripts {
+    /// Creates a new, sandboxed scripting engine.  Scripts may not import
+    /// modules, evaluate code or print, and are limited as described by
+    /// the configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Limits and script files to load
+    /// * `facts` - Facts returned by the `facts()` function
+    pub fn new(config: &ScriptConfig, facts: &Facts) -> MotdResult<Scripts> {
+        let mut engine = Engine::new();
+        let started = Rc::new(Cell::new(Instant::now()));
+
+        engine
+            .set_module_resolver(DummyModuleResolver::new())
+            .set_max_operations(config.max_operations)
+            .set_max_string_size(config.max_string_size)
+            .set_max_array_size(config.max_array_size)
+            .set_max_map_size(config.max_map_size)
+            .set_max_call_levels(config.max_call_levels)
+            .on_print(|s| debug!("script: {}", s))
+            .on_debug(|s, _, _| debug!("script: {}", s));
+        engine.disable_symbol("eval");
+
+        let timeout = Duration::from_millis(config.timeout_ms);
+        let start = Rc::clone(&started);
+        engine.on_progress(move |_| {
+            if start.get().elapsed() > timeout {
+                Some(Dynamic::from("timeout"))
+            } else {
+                None
+            }
+        });
+
+        let facts = rhai::serde::to_dynamic(facts).map_err(|_| Error::SerializationFailed)?;
+        engine.register_fn("facts", move || facts.clone());
+
+        let mut scripts = Scripts {
+            engine,
+            ast: AST::empty(),
+            started,
+        };
+
+        for file in config.files.iter() {
+            scripts.load(&fs::read_to_string(file)?)?;
+        }
+
+        Ok(scripts)
+    }
+
+    /// Compiles a script and adds the functions it defines
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Script source code
+    pub fn load(&mut self, source: &str) -> MotdResult<()> {
+        let ast = self.engine.compile(source).map_err(|e| {
+            error!("failed to compile script: {}", e);
+            Error::ScriptFailed
+        })?;
+
+        self.ast.combine(ast);
+        Ok(())
+    }
+
+    /// Calls a script-defined function.  Each comma-separated argument is
+    /// passed to the function as a string.  Returns None if no function with
+    /// that name takes that number of arguments
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the function to call
+    /// * `args` - Comma-separated list of arguments from the template
+    pub fn call(&self, name: &str, args: Option<&str>) -> Option<MotdResult<Value>> {
+        let args: Vec<String> = args
+            .map(|a| {
+                template::split_top_level(a, ',')
+                    .into_iter()
+                    .map(|arg| ansi::unquote(arg.trim()).to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let defined = self
+            .ast
+            .iter_functions()
+            .any(|f| f.name == name && f.params.len() == args.len());
+
+        if !defined {
+            return None;
+        }
+
+        // Only functions are used from scripts, so don't run their top-level
+        // statements again for every call
+        let options = CallFnOptions::new().eval_ast(false);
+
+        self.started.set(Instant::now());
+        let result = self
+            .engine
+            .call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, name, args)
+            .map_err(|e| {
+                error!("script function {} failed: {}", name, e);
+                Error::ScriptFailed
+            })
+            .and_then(|d| {
+                rhai::serde::from_dynamic::<Value>(&d).map_err(|_| Error::SerializationFailed)
+            });
+
+        Some(result)
+    }
+}
@@ -0,0 +1,341 @@
+//! Table widget for facts that hold a collection of values.
+//!
+//! ```text
+//! {{ table(ipaddr(hide_loopback), columns="name, addrs", titles="Interface, Addresses", header) }}
+//! {{ table(users, limit=5) }}
+//! ```
+//!
+//! * `columns="a, b"` - Fields of each row to show, in order.  Defaults to
+//!   every field of the first row, in the order they are declared, or the
+//!   row itself if it isn't an object
+//! * `titles="A, B"` - Header text for each column (default: field names)
+//! * `align="left, right"` - Alignment of each column: `left`, `right` or
+//!   `center` (or just `l`, `r` or `c`).  Columns default to left
+//! * `header` - Show a header row, implied by `titles`
+//! * `limit=N` - Show at most N rows, followed by a count of those left out
+
+use crate::{
+    ansi::{self, Style},
+    template::{self, ValueRef},
+    value,
+};
+use serde_json::Value;
+
+/// Field name used for rows that aren't objects
+const SELF_FIELD: &str = ".";
+
+/// Space between columns
+const COLUMN_GAP: &str = "  ";
+
+/// How the text in a column lines up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+impl Align {
+    /// Parses an alignment from its name or first letter, returning None
+    /// if it isn't recognized
+    pub fn from_name(name: &str) -> Option<Align> {
+        match name {
+            "l" | "left" => Some(Align::Left),
+            "r" | "right" => Some(Align::Right),
+            "c" | "center" => Some(Align::Center),
+            _ => None,
+        }
+    }
+
+    /// Pads text with spaces to fill `width` columns
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Text to pad
+    /// * `width` - Number of columns to fill
+    pub fn pad(self, text: &str, width: usize) -> String {
+        match self {
+            Align::Left => ansi::pad(text, width, false),
+            Align::Right => ansi::pad(text, width, true),
+            Align::Center => {
+                let left = width.saturating_sub(ansi::width(text)) / 2;
+                ansi::pad(&format!("{}{}", " ".repeat(left), text), width, false)
+            }
+        }
+    }
+}
+
+/// Arguments of a table widget
+#[derive(Clone, Debug)]
+pub struct Table {
+    /// Collection to show, one row per element
+    pub value: ValueRef,
+
+    /// Fields to show, if given
+    pub columns: Vec<String>,
+
+    /// Header text of each column, if given
+    pub titles: Vec<String>,
+
+    /// Alignment of each column
+    pub align: Vec<Align>,
+
+    /// Show a header row
+    pub header: bool,
+
+    /// Maximum number of rows to show
+    pub limit: Option<usize>,
+}
+
+/// Splits a quoted, comma-separated list (e.g. `"name, addrs"`)
+fn list(s: &str) -> Vec<String> {
+    ansi::unquote(s)
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+impl Table {
+    /// Parses the arguments of a table widget, returning None if they don't
+    /// start with a reference to a value
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments passed to the widget, exactly as written
+    pub fn parse(args: &str) -> Option<Table> {
+        let mut args = template::split_top_level(args, ',').into_iter();
+        let mut table = Table {
+            value: template::parse_ref(args.next()?)?,
+            columns: Vec::new(),
+            titles: Vec::new(),
+            align: Vec::new(),
+            header: false,
+            limit: None,
+        };
+
+        for arg in args.map(str::trim) {
+            let (key, val) = match arg.find('=') {
+                Some(i) => (arg[..i].trim(), arg[i + 1..].trim()),
+                None => (arg, ""),
+            };
+
+            match key {
+                "columns" => table.columns = list(val),
+                "titles" => {
+                    table.titles = list(val);
+                    table.header = true;
+                }
+                "align" => {
+                    table.align = list(val)
+                        .iter()
+                        .map(|a| Align::from_name(a).unwrap_or(Align::Left))
+                        .collect()
+                }
+                "header" => table.header = true,
+                "limit" => table.limit = val.parse().ok(),
+                key => log::error!("unknown table argument {}", key),
+            }
+        }
+
+        Some(table)
+    }
+
+    /// Renders the rows of a collection as a table.  Objects are shown one
+    /// per row, and anything else as a single row
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Collection to show
+    /// * `header_style` - Style of the header row
+    /// * `unicode` - Underline the header with a box drawing line instead
+    ///   of ASCII
+    pub fn render(&self, value: &Value, header_style: &Style, unicode: bool) -> String {
+        let rows: Vec<&Value> = match value {
+            Value::Array(rows) => rows.iter().collect(),
+            Value::Null => Vec::new(),
+            value => vec![value],
+        };
+
+        let columns = if !self.columns.is_empty() {
+            self.columns.clone()
+        } else {
+            match rows.first() {
+                Some(Value::Object(map)) => map.keys().cloned().collect(),
+                _ => vec![SELF_FIELD.to_string()],
+            }
+        };
+
+        let shown = self.limit.unwrap_or(rows.len()).min(rows.len());
+        let cells: Vec<Vec<String>> = rows[..shown]
+            .iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .map(|field| match field.as_str() {
+                        SELF_FIELD => value::display(row),
+                        field => value::lookup(row, field)
+                            .map(value::display)
+                            .unwrap_or_default(),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let titles: Vec<String> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, field)| match self.titles.get(i) {
+                Some(title) => title.clone(),
+                None if field == SELF_FIELD => String::new(),
+                None => field.clone(),
+            })
+            .collect();
+
+        let widths: Vec<usize> = (0..columns.len())
+            .map(|i| {
+                let title = if self.header {
+                    ansi::width(&titles[i])
+                } else {
+                    0
+                };
+                cells
+                    .iter()
+                    .map(|row| ansi::width(&row[i]))
+                    .max()
+                    .unwrap_or(0)
+                    .max(title)
+            })
+            .collect();
+
+        let line = |row: &[String]| -> String {
+            let cols: Vec<String> = row
+                .iter()
+                .enumerate()
+                .map(|(i, cell)| {
+                    let align = self.align.get(i).copied().unwrap_or(Align::Left);
+                    align.pad(cell, widths[i])
+                })
+                .collect();
+            cols.join(COLUMN_GAP).trim_end().to_string()
+        };
+
+        let mut lines = Vec::new();
+        if self.header {
+            lines.push(ansi::paint(&line(&titles), header_style));
+
+            let rule = if unicode { "─" } else { "-" };
+            let rules: Vec<String> = widths.iter().map(|w| rule.repeat(*w)).collect();
+            lines.push(rules.join(COLUMN_GAP));
+        }
+
+        lines.extend(cells.iter().map(|row| line(row)));
+        if shown < rows.len() {
+            lines.push(format!("... and {} more", rows.len() - shown));
+        }
+
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(args: &str, value: Value) -> String {
+        Table::parse(args)
+            .unwrap()
+            .render(&value, &Style::default(), true)
+    }
+
+    fn disks() -> Value {
+        json!([
+            { "path": "/", "used_pct": 71.5, "total": 100 },
+            { "path": "/home", "used_pct": 5, "total": 2000 },
+            { "path": "/boot", "used_pct": 40, "total": 1 },
+        ])
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let table = Table::parse(
+            r#"disk("/a, b").parts, columns="path, total", align="r, c, x", limit=2, bogus"#,
+        )
+        .unwrap();
+        assert_eq!(table.value.call.name, "disk");
+        assert_eq!(table.value.field.as_deref(), Some("parts"));
+        assert_eq!(table.columns, vec!["path", "total"]);
+        assert_eq!(table.align, vec![Align::Right, Align::Center, Align::Left]);
+        assert_eq!(table.limit, Some(2));
+        assert!(!table.header);
+
+        let table = Table::parse(r#"users, titles="Name""#).unwrap();
+        assert_eq!(table.titles, vec!["Name"]);
+        assert!(table.header);
+        assert!(Table::parse("").is_none());
+    }
+
+    #[test]
+    fn renders_fields_in_declared_order() {
+        assert_eq!(
+            render("disk", disks()),
+            "/      71.5  100\n/home  5     2000\n/boot  40    1"
+        );
+    }
+
+    #[test]
+    fn selects_columns() {
+        assert_eq!(
+            render(
+                r#"disk, columns="total, path, missing", align="r""#,
+                disks()
+            ),
+            " 100  /\n2000  /home\n   1  /boot"
+        );
+    }
+
+    #[test]
+    fn renders_header() {
+        assert_eq!(
+            render(
+                r#"disk, columns="path, used_pct", header, align="l, c""#,
+                disks()
+            ),
+            "path   used_pct\n─────  ────────\n/        71.5\n/home     5\n/boot     40"
+        );
+        let table = Table::parse(r#"disk, columns="path", titles="Mount""#).unwrap();
+        let bold = Style {
+            bold: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            table.render(&disks(), &bold, false),
+            "\x1b[1mMount\x1b[0m\n-----\n/\n/home\n/boot"
+        );
+    }
+
+    #[test]
+    fn limits_rows() {
+        assert_eq!(
+            render(r#"disk, columns="path", limit=1"#, disks()),
+            "/\n... and 2 more"
+        );
+        assert_eq!(
+            render(r#"disk, columns="path", limit=0"#, disks()),
+            "... and 3 more"
+        );
+        assert_eq!(
+            render(r#"disk, columns="path", limit=5"#, disks()),
+            "/\n/home\n/boot"
+        );
+    }
+
+    #[test]
+    fn renders_values_as_rows() {
+        assert_eq!(render("users", json!(["root", "kevin"])), "root\nkevin");
+        assert_eq!(render("users, header", json!(["root"])), "\n────\nroot");
+        assert_eq!(render("uptime", json!(42)), "42");
+        assert_eq!(render("uptime", Value::Null), "");
+    }
+}
@@ -0,0 +1,644 @@
+//! Parser for motd templates.
+//!
+//! A template is plain text that may contain:
+//!
+//! * `{{ command }}` or `{{ command(args) }}` - replaced with the output of
+//!   a command (e.g., `{{ hostname }}` or `{{ ipaddr(addr_only) }}`)
+//! * `{{ command | filter(args) | ... }}` - the output of the command passed
+//!   through each filter, left to right (e.g., `{{ hostname | fg(green) }}`)
+//! * `{% tag args %}...{% endtag %}` - a block whose rendered body is
+//!   transformed by the tag (e.g., `{% style fg=cyan bold %}`)
+//! * `{% script %}...{% endscript %}` - script source code, which is never
+//!   rendered
+//!
+//! A block tag that is alone on its line (ignoring whitespace) is removed
+//! along with the line, so tags don't leave blank lines behind.
+
+use crate::error::{MotdResult, ParsingError};
+use log::error;
+use unicode_width::UnicodeWidthChar;
+
+/// A command or filter along with the arguments passed to it.  Arguments
+/// are kept exactly as written between the parentheses
+#[derive(Clone, Debug)]
+pub struct Call {
+    /// Name of the command or filter
+    pub name: String,
+
+    /// Everything between the parentheses, if there were any
+    pub args: Option<String>,
+}
+
+/// An expression (`{{ ... }}`): a command followed by any number of filters
+#[derive(Clone, Debug)]
+pub struct Expr {
+    /// Command that produces the value
+    pub call: Call,
+
+    /// Filters to apply to the value, in order
+    pub filters: Vec<Call>,
+}
+
+/// A reference to a value, passed as the argument of a widget: a command
+/// followed by an optional path to a field of its value (e.g.,
+/// `mem.used_pct` or `disk("/").used_pct`)
+#[derive(Clone, Debug)]
+pub struct ValueRef {
+    /// Command that produces the value
+    pub call: Call,
+
+    /// Dot separated path of the field, if any
+    pub field: Option<String>,
+}
+
+/// A single piece of a parsed template
+#[derive(Clone, Debug)]
+pub enum Node {
+    /// Plain text, rendered as-is
+    Text(String),
+
+    /// An expression to evaluate
+    Expr(Expr),
+
+    /// A block tag and the nodes between it and its end tag
+    Block {
+        tag: String,
+        args: String,
+        body: Vec<Node>,
+    },
+
+    /// Source code of a script block
+    Script(String),
+}
+
+/// Parses a template into nodes
+///
+/// # Arguments
+///
+/// * `src` - Contents of the template
+pub fn parse(src: &str) -> MotdResult<Vec<Node>> {
+    let mut parser = Parser { src, pos: 0 };
+    parser.nodes(None)
+}
+
+/// Returns all script sources in the template, including those nested
+/// in blocks, in the order they appear
+///
+/// # Arguments
+///
+/// * `nodes` - Parsed template
+pub fn scripts(nodes: &[Node]) -> Vec<&str> {
+    let mut sources = Vec::new();
+    for node in nodes {
+        match node {
+            Node::Script(src) => sources.push(src.as_str()),
+            Node::Block { body, .. } => sources.extend(scripts(body)),
+            _ => {}
+        }
+    }
+
+    sources
+}
+
+/// Splits `s` on every `sep` that is not inside quotes or parentheses
+///
+/// # Arguments
+///
+/// * `s` - String to split
+/// * `sep` - Character to split on
+pub fn split_top_level(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, c) if c == sep && depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Parses a reference to a value, e.g. `mem.used_pct` or
+/// `disk("/").used_pct`
+///
+/// # Arguments
+///
+/// * `s` - Text of the reference
+pub fn parse_ref(s: &str) -> Option<ValueRef> {
+    let s = s.trim();
+    let name_len = name_len(s);
+    if name_len == 0 {
+        return None;
+    }
+
+    // The arguments end at the parenthesis that closes the first one
+    let mut end = name_len;
+    if s[name_len..].starts_with('(') {
+        let mut depth = 0;
+        let mut quote: Option<char> = None;
+        let close = s[name_len..].char_indices().find(|&(_, c)| {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '"') | (None, '\'') => quote = Some(c),
+                (None, '(') => depth += 1,
+                (None, ')') => depth -= 1,
+                _ => {}
+            }
+            quote.is_none() && depth == 0
+        })?;
+        end = name_len + close.0 + 1;
+    }
+
+    let call = parse_call(&s[..end])?;
+    let field = match &s[end..] {
+        "" => None,
+        rest => Some(rest.strip_prefix('.')?.to_string()),
+    };
+
+    Some(ValueRef { call, field })
+}
+
+/// Returns the length of the command or filter name at the start of `s`
+///
+/// # Arguments
+///
+/// * `s` - Text starting with a name
+fn name_len(s: &str) -> usize {
+    s.char_indices()
+        .find(|(i, c)| !(c.is_ascii_alphabetic() || (*i > 0 && (c.is_ascii_digit() || *c == '_'))))
+        .map(|(i, _)| i)
+        .unwrap_or_else(|| s.len())
+}
+
+/// Parses a command or filter, e.g. `name` or `name(args)`
+///
+/// # Arguments
+///
+/// * `s` - Text of the call
+fn parse_call(s: &str) -> Option<Call> {
+    let s = s.trim();
+    let name_len = name_len(s);
+    if name_len == 0 {
+        return None;
+    }
+
+    let name = s[..name_len].to_string();
+    let rest = s[name_len..].trim();
+    if rest.is_empty() {
+        return Some(Call { name, args: None });
+    }
+
+    if rest.starts_with('(') && rest.ends_with(')') {
+        let args = rest[1..rest.len() - 1].to_string();
+        return Some(Call {
+            name,
+            args: Some(args),
+        });
+    }
+
+    None
+}
+
+/// Parses the inside of an expression (`{{ ... }}`)
+///
+/// # Arguments
+///
+/// * `s` - Text between the braces
+fn parse_expr(s: &str) -> Option<Expr> {
+    let mut calls = split_top_level(s, '|').into_iter().map(parse_call);
+    let call = calls.next()??;
+    let filters = calls.collect::<Option<Vec<Call>>>()?;
+
+    Some(Expr { call, filters })
+}
+
+/// Recursive descent parser over the template source
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// If the tag spanning `start..end` is alone on its line, returns the
+    /// range to remove instead: from the start of the line through the
+    /// newline following the tag
+    fn standalone(&self, start: usize, end: usize) -> (usize, usize) {
+        let line_start = self.src[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        if !self.src[line_start..start].trim().is_empty() {
+            return (start, end);
+        }
+
+        let rest = &self.src[end..];
+        let trailing = rest.len() - rest.trim_start_matches([' ', '\t'].as_ref()).len();
+        let rest = &rest[trailing..];
+
+        if rest.starts_with("\r\n") {
+            (line_start, end + trailing + 2)
+        } else if rest.starts_with('\n') {
+            (line_start, end + trailing + 1)
+        } else if rest.is_empty() {
+            (line_start, end + trailing)
+        } else {
+            (start, end)
+        }
+    }
+
+    /// Parses nodes until the end of the template, or until the end tag
+    /// `end` is found
+    ///
+    /// # Arguments
+    ///
+    /// * `end` - End tag that closes the current block, if inside one
+    fn nodes(&mut self, end: Option<&str>) -> MotdResult<Vec<Node>> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+
+        loop {
+            let rest = &self.src[self.pos..];
+            let next = match (rest.find("{{"), rest.find("{%")) {
+                (Some(e), Some(b)) => Some(e.min(b)),
+                (e, b) => e.or(b),
+            };
+
+            let start = match next {
+                Some(offset) => self.pos + offset,
+                None => {
+                    text.push_str(rest);
+                    self.pos = self.src.len();
+
+                    if let Some(end) = end {
+                        error!("template is missing {{% {} %}}", end);
+                        return Err(ParsingError::InvalidTemplate.into());
+                    }
+
+                    break;
+                }
+            };
+
+            if self.src[start..].starts_with("{{") {
+                // An expression that can't be parsed is left as plain text
+                let close = self.src[start..].find("}}").map(|i| start + i);
+                let expr = close.and_then(|close| parse_expr(&self.src[start + 2..close]));
+                match (close, expr) {
+                    (Some(close), Some(expr)) => {
+                        text.push_str(&self.src[self.pos..start]);
+                        if !text.is_empty() {
+                            nodes.push(Node::Text(std::mem::take(&mut text)));
+                        }
+                        nodes.push(Node::Expr(expr));
+                        self.pos = close + 2;
+                    }
+                    _ => {
+                        text.push_str(&self.src[self.pos..start + 2]);
+                        self.pos = start + 2;
+                    }
+                }
+
+                continue;
+            }
+
+            let close = match self.src[start..].find("%}") {
+                Some(i) => start + i,
+                None => {
+                    text.push_str(&self.src[self.pos..start + 2]);
+                    self.pos = start + 2;
+                    continue;
+                }
+            };
+
+            let inner = self.src[start + 2..close].trim();
+            let (tag, args) = match inner.find(char::is_whitespace) {
+                Some(i) => (&inner[..i], inner[i..].trim()),
+                None => (inner, ""),
+            };
+
+            let (remove_start, remove_end) = self.standalone(start, close + 2);
+            text.push_str(&self.src[self.pos..remove_start]);
+            if !text.is_empty() {
+                nodes.push(Node::Text(std::mem::take(&mut text)));
+            }
+            self.pos = remove_end;
+
+            if tag.starts_with("end") {
+                if Some(tag) == end {
+                    return Ok(nodes);
+                }
+
+                error!("unexpected {{% {} %}} in template", tag);
+                return Err(ParsingError::InvalidTemplate.into());
+            }
+
+            if tag == "script" {
+                let rest = &self.src[self.pos..];
+                let close = match rest.find("{% endscript %}") {
+                    Some(i) => self.pos + i,
+                    None => {
+                        error!("template is missing {{% endscript %}}");
+                        return Err(ParsingError::InvalidTemplate.into());
+                    }
+                };
+
+                let (body_end, after) = self.standalone(close, close + "{% endscript %}".len());
+                nodes.push(Node::Script(self.src[self.pos..body_end].to_string()));
+                self.pos = after;
+                continue;
+            }
+
+            let end_tag = format!("end{}", tag);
+            let body = self.nodes(Some(&end_tag))?;
+            nodes.push(Node::Block {
+                tag: tag.to_string(),
+                args: args.to_string(),
+                body,
+            });
+        }
+
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+
+        Ok(nodes)
+    }
+}
+
+/// Removes the first `width` columns of every line of the literal text in
+/// the nodes, e.g. to drop the art drawn to the left of the information in
+/// a template.  Past the removed columns, a line is only kept if it
+/// contains an expression or block, so art that extends beyond the
+/// columns doesn't leave fragments behind.  Blank lines are always kept
+///
+/// # Arguments
+///
+/// * `nodes` - Nodes to remove the columns from
+/// * `width` - Number of columns to remove
+pub fn drop_columns(nodes: &[Node], width: usize) -> Vec<Node> {
+    let mut columns = Columns {
+        width,
+        col: 0,
+        anchored: false,
+        blank: true,
+        pending: String::new(),
+    };
+
+    columns.nodes(nodes)
+}
+
+/// State for `drop_columns`, carried across nodes since a line may span
+/// several of them
+struct Columns {
+    /// Number of columns to remove
+    width: usize,
+
+    /// Current column of the line in the template source
+    col: usize,
+
+    /// An expression or block was found past the removed columns of this
+    /// line, so the rest of the line is kept
+    anchored: bool,
+
+    /// Nothing but whitespace has been seen on this line
+    blank: bool,
+
+    /// Text past the removed columns that will only be written if the line
+    /// turns out to be anchored
+    pending: String,
+}
+
+impl Columns {
+    /// Marks the line as kept, returning any text held back until now
+    fn anchor(&mut self) -> Option<Node> {
+        self.anchored = true;
+        self.blank = false;
+        self.col = self.col.max(self.width);
+
+        if self.pending.is_empty() {
+            None
+        } else {
+            Some(Node::Text(std::mem::take(&mut self.pending)))
+        }
+    }
+
+    /// Removes the columns from a list of nodes, descending into blocks
+    fn nodes(&mut self, nodes: &[Node]) -> Vec<Node> {
+        let mut out = Vec::new();
+        for node in nodes {
+            match node {
+                Node::Text(text) => {
+                    let text = self.text(text);
+                    if !text.is_empty() {
+                        out.push(Node::Text(text));
+                    }
+                }
+                Node::Expr(_) => {
+                    out.extend(self.anchor());
+                    out.push(node.clone());
+                }
+                Node::Block { tag, args, body } => {
+                    // Blocks that start within the removed columns usually
+                    // wrap the art itself, so only those past it anchor
+                    if self.col >= self.width {
+                        out.extend(self.anchor());
+                    }
+
+                    out.push(Node::Block {
+                        tag: tag.clone(),
+                        args: args.clone(),
+                        body: self.nodes(body),
+                    });
+                }
+                Node::Script(_) => out.push(node.clone()),
+            }
+        }
+
+        out
+    }
+
+    /// Removes the columns from literal text
+    fn text(&mut self, text: &str) -> String {
+        let mut s = String::new();
+        for c in text.chars() {
+            if c == '\n' {
+                if self.anchored || self.blank {
+                    s.push('\n');
+                }
+
+                self.col = 0;
+                self.anchored = false;
+                self.blank = true;
+                self.pending.clear();
+                continue;
+            }
+
+            self.blank &= c.is_whitespace();
+            if self.anchored {
+                s.push(c);
+            } else if self.col < self.width {
+                self.col += c.width().unwrap_or(0);
+            } else {
+                self.pending.push(c);
+            }
+        }
+
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_outside_quotes_and_parentheses() {
+        assert_eq!(split_top_level("a, b,c", ','), vec!["a", " b", "c"]);
+        assert_eq!(
+            split_top_level(r#"dir="a, b", f(1, 2), 'c,d'"#, ','),
+            vec![r#"dir="a, b""#, " f(1, 2)", " 'c,d'"]
+        );
+        assert_eq!(
+            split_top_level("f(g(1, 2), 3)|h", '|'),
+            vec!["f(g(1, 2), 3)", "h"]
+        );
+        assert_eq!(split_top_level("", ','), vec![""]);
+    }
+
+    #[test]
+    fn keeps_unterminated_quotes_together() {
+        assert_eq!(split_top_level(r#"a, "b, c"#, ','), vec!["a", r#" "b, c"#]);
+    }
+
+    #[test]
+    fn parses_expressions_and_filters() {
+        let nodes = parse("Hi {{ user }}, {{ ipaddr(addr_only) | fg(\"a|b\") | bold }}!").unwrap();
+        assert_eq!(nodes.len(), 5);
+        match &nodes[1] {
+            Node::Expr(expr) => {
+                assert_eq!(expr.call.name, "user");
+                assert!(expr.call.args.is_none());
+                assert!(expr.filters.is_empty());
+            }
+            node => panic!("expected an expression, got {:?}", node),
+        }
+        match &nodes[3] {
+            Node::Expr(expr) => {
+                assert_eq!(expr.call.name, "ipaddr");
+                assert_eq!(expr.call.args.as_deref(), Some("addr_only"));
+                let filters: Vec<(&str, Option<&str>)> = expr
+                    .filters
+                    .iter()
+                    .map(|f| (f.name.as_str(), f.args.as_deref()))
+                    .collect();
+                assert_eq!(filters, vec![("fg", Some("\"a|b\"")), ("bold", None)]);
+            }
+            node => panic!("expected an expression, got {:?}", node),
+        }
+    }
+
+    #[test]
+    fn parses_value_references() {
+        let r = parse_ref(" mem.used_pct ").unwrap();
+        assert_eq!(r.call.name, "mem");
+        assert!(r.call.args.is_none());
+        assert_eq!(r.field.as_deref(), Some("used_pct"));
+
+        let r = parse_ref(r#"disk("/(a)").used_pct"#).unwrap();
+        assert_eq!(r.call.name, "disk");
+        assert_eq!(r.call.args.as_deref(), Some(r#""/(a)""#));
+        assert_eq!(r.field.as_deref(), Some("used_pct"));
+
+        let r = parse_ref("f(g(1), ')').a.0").unwrap();
+        assert_eq!(r.call.args.as_deref(), Some("g(1), ')'"));
+        assert_eq!(r.field.as_deref(), Some("a.0"));
+
+        assert!(parse_ref("cpu").unwrap().field.is_none());
+        assert!(parse_ref("").is_none());
+        assert!(parse_ref("disk(/").is_none());
+        assert!(parse_ref("disk(/)x").is_none());
+    }
+
+    #[test]
+    fn parses_calls() {
+        let call = parse_call(" disk_2(\"/home\", 'a)') ").unwrap();
+        assert_eq!(call.name, "disk_2");
+        assert_eq!(call.args.as_deref(), Some("\"/home\", 'a)'"));
+        assert!(parse_call("").is_none());
+        assert!(parse_call("2fast").is_none());
+        assert!(parse_call("name(args").is_none());
+        assert!(parse_call("name args").is_none());
+    }
+
+    #[test]
+    fn nests_blocks() {
+        let src = "{% style bold %}\na{% style fg=red %}b{% endstyle %}c\n{% endstyle %}\n";
+        let nodes = parse(src).unwrap();
+        assert_eq!(nodes.len(), 1);
+        match &nodes[0] {
+            Node::Block { tag, args, body } => {
+                assert_eq!(tag, "style");
+                assert_eq!(args, "bold");
+                assert_eq!(body.len(), 3);
+                match &body[1] {
+                    Node::Block { args, body, .. } => {
+                        assert_eq!(args, "fg=red");
+                        assert!(matches!(&body[..], [Node::Text(t)] if t == "b"));
+                    }
+                    node => panic!("expected a block, got {:?}", node),
+                }
+                assert!(matches!(&body[2], Node::Text(t) if t == "c\n"));
+            }
+            node => panic!("expected a block, got {:?}", node),
+        }
+    }
+
+    #[test]
+    fn removes_standalone_tag_lines() {
+        let nodes = parse("a\n  {% style bold %}  \r\nb\n{% endstyle %}").unwrap();
+        match &nodes[..] {
+            [Node::Text(a), Node::Block { body, .. }] => {
+                assert_eq!(a, "a\n");
+                assert!(matches!(&body[..], [Node::Text(t)] if t == "b\n"));
+            }
+            nodes => panic!("unexpected nodes {:?}", nodes),
+        }
+    }
+
+    #[test]
+    fn collects_scripts() {
+        let src = "{% script %}\nfn a() {}\n{% endscript %}\n{% style bold %}{% script %}b{% endscript %}{% endstyle %}";
+        let nodes = parse(src).unwrap();
+        assert_eq!(scripts(&nodes), vec!["fn a() {}\n", "b"]);
+    }
+
+    #[test]
+    fn keeps_unterminated_tags_as_text() {
+        let nodes = parse("a {{ user b {% style").unwrap();
+        match &nodes[..] {
+            [Node::Text(t)] => assert_eq!(t, "a {{ user b {% style"),
+            nodes => panic!("unexpected nodes {:?}", nodes),
+        }
+
+        let nodes = parse("{{ not valid! }}").unwrap();
+        assert!(matches!(&nodes[..], [Node::Text(t)] if t == "{{ not valid! }}"));
+    }
+
+    #[test]
+    fn rejects_unbalanced_blocks() {
+        assert!(parse("{% style bold %}a").is_err());
+        assert!(parse("a{% endstyle %}").is_err());
+        assert!(parse("{% style bold %}a{% endpanel %}").is_err());
+        assert!(parse("{% script %}a").is_err());
+    }
+}
@@ -0,0 +1,438 @@
+//! Detects what the terminal motd is printing to is capable of, and adapts
+//! rendered output to match
+
+use crate::ansi::{self, Color, Style};
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+};
+
+/// Number of colors a terminal can display
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorDepth {
+    /// No color or styling at all
+    None,
+
+    /// The 16 standard colors
+    Basic,
+
+    /// The 256-color palette
+    Indexed,
+
+    /// 24-bit color
+    TrueColor,
+}
+
+/// What the terminal is capable of displaying
+#[derive(Clone, Copy, Debug)]
+pub struct Capabilities {
+    /// Colors the terminal supports
+    pub color: ColorDepth,
+
+    /// True if the terminal can display Unicode (e.g., box drawing characters)
+    pub unicode: bool,
+
+    /// Number of columns, if known
+    pub width: Option<usize>,
+
+    /// Number of lines, if known
+    pub height: Option<usize>,
+}
+
+impl Default for Capabilities {
+    fn default() -> Capabilities {
+        Capabilities {
+            color: ColorDepth::TrueColor,
+            unicode: true,
+            width: None,
+            height: None,
+        }
+    }
+}
+
+/// Returns the value of an environment variable if it is set and not empty
+fn var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+/// Returns true if stdout is a terminal
+fn stdout_is_tty() -> bool {
+    io::stdout().is_terminal()
+}
+
+/// Returns the size (columns, lines) of the terminal connected to stdout,
+/// falling back to `COLUMNS` and `LINES` if stdout isn't a terminal
+fn terminal_size() -> (Option<usize>, Option<usize>) {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let res = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if res == 0 && size.ws_col > 0 {
+        return (Some(size.ws_col as usize), Some(size.ws_row as usize));
+    }
+
+    let env_size = |name: &str| var(name).and_then(|v| v.parse().ok()).filter(|n| *n > 0);
+    (env_size("COLUMNS"), env_size("LINES"))
+}
+
+/// Finds the compiled terminfo entry for a terminal, searching the same
+/// directories as ncurses
+///
+/// # Arguments
+///
+/// * `term` - Name of the terminal (i.e., `$TERM`)
+fn terminfo_path(term: &str) -> Option<PathBuf> {
+    let first = term.chars().next()?;
+
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    if let Some(dir) = var("TERMINFO") {
+        dirs.push(dir.into());
+    }
+
+    if let Some(home) = var("HOME") {
+        dirs.push(Path::new(&home).join(".terminfo"));
+    }
+
+    if let Some(list) = var("TERMINFO_DIRS") {
+        dirs.extend(list.split(':').filter(|d| !d.is_empty()).map(PathBuf::from));
+    }
+
+    dirs.extend(
+        ["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo"]
+            .iter()
+            .map(PathBuf::from),
+    );
+
+    dirs.into_iter()
+        .flat_map(|dir| {
+            vec![
+                dir.join(first.to_string()).join(term),
+                dir.join(format!("{:x}", first as u32)).join(term),
+            ]
+        })
+        .find(|path| path.is_file())
+}
+
+/// Reads the number of colors (the `colors` capability) from a terminal's
+/// compiled terminfo entry
+///
+/// # Arguments
+///
+/// * `term` - Name of the terminal (i.e., `$TERM`)
+fn terminfo_colors(term: &str) -> Option<i32> {
+    let data = fs::read(terminfo_path(term)?).ok()?;
+    parse_colors(&data)
+}
+
+/// Parses the number of colors (the `colors` capability) out of a compiled
+/// terminfo entry.  See term(5) for the file format
+///
+/// # Arguments
+///
+/// * `data` - Contents of the terminfo file
+fn parse_colors(data: &[u8]) -> Option<i32> {
+    /// Index of `colors` in the numbers section
+    const COLORS: usize = 13;
+
+    let short = |i: usize| -> Option<i32> {
+        let b = data.get(i * 2..i * 2 + 2)?;
+        Some(i16::from_le_bytes([b[0], b[1]]) as i32)
+    };
+
+    // The extended format stores numbers as 32-bit integers
+    let number_size = match short(0)? {
+        0o432 => 2,
+        0o1036 => 4,
+        _ => return None,
+    };
+
+    let names_size = short(1)? as usize;
+    let bools_count = short(2)? as usize;
+    let nums_count = short(3)? as usize;
+    if COLORS >= nums_count {
+        return None;
+    }
+
+    // Numbers start on an even byte after the header, names and booleans
+    let mut offset = 12 + names_size + bools_count;
+    offset += offset % 2;
+    offset += COLORS * number_size;
+
+    let b = data.get(offset..offset + number_size)?;
+    let colors = if number_size == 2 {
+        i16::from_le_bytes([b[0], b[1]]) as i32
+    } else {
+        i32::from_le_bytes([b[0], b[1], b[2], b[3]])
+    };
+
+    Some(colors)
+}
+
+impl Capabilities {
+    /// Detects the capabilities of the terminal connected to stdout.
+    ///
+    /// Color is always disabled if `NO_COLOR` is set.  Otherwise, color is
+    /// disabled if `CLICOLOR` is `0` or stdout isn't a terminal, unless
+    /// `CLICOLOR_FORCE` is set to anything but `0`.  The color depth is taken
+    /// from `COLORTERM`, then `TERM` and its terminfo entry.  Unicode is
+    /// assumed unless the locale's charset is something other than UTF-8.
+    /// The size is read from the terminal, then `COLUMNS` and `LINES`
+    pub fn detect() -> Capabilities {
+        let forced = var("CLICOLOR_FORCE").map(|v| v != "0").unwrap_or(false);
+        let disabled = var("CLICOLOR").map(|v| v == "0").unwrap_or(false) || !stdout_is_tty();
+
+        let color = if var("NO_COLOR").is_some() || (disabled && !forced) {
+            ColorDepth::None
+        } else {
+            Capabilities::detect_color_depth(forced)
+        };
+
+        let locale = var("LC_ALL")
+            .or_else(|| var("LC_CTYPE"))
+            .or_else(|| var("LANG"));
+        let unicode = supports_unicode(locale.as_deref());
+
+        let (width, height) = terminal_size();
+
+        Capabilities {
+            color,
+            unicode,
+            width,
+            height,
+        }
+    }
+
+    /// Determines how many colors the terminal supports
+    ///
+    /// # Arguments
+    ///
+    /// * `forced` - Color was forced on, so never report no color
+    fn detect_color_depth(forced: bool) -> ColorDepth {
+        if let Some(colorterm) = var("COLORTERM") {
+            if colorterm == "truecolor" || colorterm == "24bit" {
+                return ColorDepth::TrueColor;
+            }
+        }
+
+        let term = var("TERM").unwrap_or_default();
+        let depth = match terminfo_colors(&term) {
+            Some(n) if n >= 1 << 24 => ColorDepth::TrueColor,
+            Some(n) if n >= 256 => ColorDepth::Indexed,
+            Some(n) if n >= 8 => ColorDepth::Basic,
+            Some(_) => ColorDepth::None,
+            None if term.contains("256color") => ColorDepth::Indexed,
+            None if term.is_empty() || term == "dumb" => ColorDepth::None,
+            None => ColorDepth::Basic,
+        };
+
+        if forced && depth == ColorDepth::None {
+            ColorDepth::Basic
+        } else {
+            depth
+        }
+    }
+
+    /// Adapts rendered text to what the terminal can display: colors are
+    /// reduced to the supported depth (or removed), and box drawing and
+    /// block characters are replaced with ASCII if Unicode is unsupported
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Rendered text to adapt
+    pub fn adapt(&self, text: &str) -> String {
+        let text = match self.color {
+            ColorDepth::TrueColor => text.to_string(),
+            ColorDepth::None => ansi::strip(text),
+            depth => ansi::map_styles(text, |style| downsample(style, depth)),
+        };
+
+        if self.unicode {
+            text
+        } else {
+            text.chars().map(to_ascii).collect()
+        }
+    }
+}
+
+/// Returns true if a locale (e.g., `en_US.UTF-8`) uses the UTF-8 charset.
+/// Without a locale the charset is unknown, and Unicode is assumed
+///
+/// # Arguments
+///
+/// * `locale` - Value of `LC_ALL`, `LC_CTYPE` or `LANG`, if any is set
+fn supports_unicode(locale: Option<&str>) -> bool {
+    match locale.map(str::to_lowercase) {
+        Some(locale) => locale.contains("utf-8") || locale.contains("utf8"),
+        None => true,
+    }
+}
+
+/// Reduces the colors of a style to the given depth
+///
+/// # Arguments
+///
+/// * `style` - Style to reduce
+/// * `depth` - Color depth to reduce to
+fn downsample(style: Style, depth: ColorDepth) -> Style {
+    let reduce = |c: Color| match depth {
+        ColorDepth::Basic => c.to_basic(),
+        _ => c.to_indexed(),
+    };
+
+    Style {
+        fg: style.fg.map(reduce),
+        bg: style.bg.map(reduce),
+        ..style
+    }
+}
+
+/// Replaces box drawing and block element characters with the closest
+/// ASCII character.  All other characters are returned as-is
+///
+/// # Arguments
+///
+/// * `c` - Character to replace
+pub fn to_ascii(c: char) -> char {
+    match c {
+        '─' | '━' | '┄' | '┅' | '┈' | '┉' | '╌' | '╍' | '═' | '╴' | '╶' | '╸' | '╺' => {
+            '-'
+        }
+        '│' | '┃' | '┆' | '┇' | '┊' | '┋' | '╎' | '╏' | '║' | '╵' | '╷' | '╹' | '╻' => {
+            '|'
+        }
+        '\u{250C}'..='\u{254B}' | '\u{2552}'..='\u{256C}' => '+',
+        '╭' | '╮' | '╯' | '╰' => '+',
+        '╱' => '/',
+        '╲' => '\\',
+        '╳' => 'X',
+        '█' | '▓' | '▉' | '▊' | '▇' | '▆' => '#',
+        '▒' | '▌' | '▐' | '▋' | '▅' | '▄' | '▀' => '=',
+        '░' | '▍' | '▎' | '▏' | '▃' | '▂' | '▁' => '-',
+        '·' | '•' | '●' => '*',
+        c if ('\u{2800}'..='\u{28FF}').contains(&c) => {
+            if c == '\u{2800}' {
+                ' '
+            } else {
+                ':'
+            }
+        }
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a compiled terminfo entry with the given numbers
+    fn entry(magic: u16, names: &str, bools: usize, numbers: &[i32], wide: bool) -> Vec<u8> {
+        let mut data = Vec::new();
+        for n in [
+            magic,
+            names.len() as u16 + 1,
+            bools as u16,
+            numbers.len() as u16,
+            0,
+            0,
+        ] {
+            data.extend_from_slice(&n.to_le_bytes());
+        }
+        data.extend_from_slice(names.as_bytes());
+        data.push(0);
+        data.resize(data.len() + bools, 1);
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+        for n in numbers {
+            if wide {
+                data.extend_from_slice(&n.to_le_bytes());
+            } else {
+                data.extend_from_slice(&(*n as i16).to_le_bytes());
+            }
+        }
+        data
+    }
+
+    /// Numbers with `colors` (index 13) set
+    fn numbers(colors: i32) -> Vec<i32> {
+        let mut numbers = vec![-1; 15];
+        numbers[13] = colors;
+        numbers
+    }
+
+    #[test]
+    fn parses_legacy_format() {
+        let data = entry(0o432, "xterm-256color|xterm", 38, &numbers(256), false);
+        assert_eq!(parse_colors(&data), Some(256));
+    }
+
+    #[test]
+    fn parses_extended_number_format() {
+        let data = entry(
+            0o1036,
+            "xterm-direct|direct color",
+            38,
+            &numbers(1 << 24),
+            true,
+        );
+        assert_eq!(parse_colors(&data), Some(1 << 24));
+    }
+
+    #[test]
+    fn aligns_numbers_to_even_offset() {
+        // 12 + 6 + 3 = 21, so a padding byte precedes the numbers
+        let data = entry(0o432, "vt100", 3, &numbers(8), false);
+        assert_eq!((12 + 6 + 3) % 2, 1);
+        assert_eq!(parse_colors(&data), Some(8));
+    }
+
+    #[test]
+    fn missing_colors_capability() {
+        let data = entry(0o432, "dumb", 2, &[80, -1], false);
+        assert_eq!(parse_colors(&data), None);
+
+        let data = entry(0o432, "dumb", 2, &numbers(-1), false);
+        assert_eq!(parse_colors(&data), Some(-1));
+    }
+
+    #[test]
+    fn rejects_bad_magic_and_truncated_data() {
+        let data = entry(0o433, "xterm", 38, &numbers(8), false);
+        assert_eq!(parse_colors(&data), None);
+
+        let data = entry(0o432, "xterm", 38, &numbers(8), false);
+        assert_eq!(parse_colors(&data[..data.len() - 4]), None);
+        assert_eq!(parse_colors(&data[..3]), None);
+        assert_eq!(parse_colors(&[]), None);
+    }
+
+    #[test]
+    fn detects_unicode_locales() {
+        assert!(supports_unicode(Some("en_US.UTF-8")));
+        assert!(supports_unicode(Some("de_DE.utf8")));
+        assert!(supports_unicode(None));
+        assert!(!supports_unicode(Some("C")));
+        assert!(!supports_unicode(Some("POSIX")));
+        assert!(!supports_unicode(Some("en_US.ISO-8859-1")));
+    }
+
+    #[test]
+    fn adapts_to_capabilities() {
+        let text = "\x1b[38;2;255;0;0m─│█\x1b[0m";
+        let caps = |color, unicode| Capabilities {
+            color,
+            unicode,
+            ..Default::default()
+        };
+
+        assert_eq!(caps(ColorDepth::TrueColor, true).adapt(text), text);
+        assert_eq!(
+            caps(ColorDepth::Indexed, true).adapt(text),
+            "\x1b[38;5;196m─│█\x1b[0m"
+        );
+        assert_eq!(
+            caps(ColorDepth::Basic, false).adapt(text),
+            "\x1b[91m-|#\x1b[0m"
+        );
+        assert_eq!(caps(ColorDepth::None, false).adapt(text), "-|#");
+    }
+}
@@ -0,0 +1,212 @@
+//! Named color themes.
+//!
+//! A theme is a TOML file that maps semantic roles used by templates (such as
+//! `label`, `value` or `art`) to styles, along with an optional palette of
+//! named colors the styles may refer to:
+//!
+//! ```toml
+//! name = "solarized"
+//!
+//! [palette]
+//! blue = "#268bd2"
+//!
+//! [roles]
+//! label = "fg=blue bold"
+//! ```
+//!
+//! Templates then use roles anywhere a style is accepted, e.g.
+//! `{% style label %}` or `{{ uptime | style(value) }}`.
+
+use crate::{
+    ansi::{Color, Style},
+    error::{Error, MotdResult},
+};
+use log::error;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Directories searched, in order, for a theme by name
+pub const THEME_DIRS: [&str; 3] = ["themes", "/etc/motd-rs/themes", "/usr/share/motd-rs/themes"];
+
+/// Roles may refer to other roles, but only this deep
+const MAX_DEPTH: usize = 8;
+
+/// A set of styles for the semantic roles used in templates
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Name of the theme
+    pub name: String,
+
+    /// Named colors, usable anywhere a color is accepted (e.g., `fg=base03`)
+    pub palette: HashMap<String, String>,
+
+    /// Style of each role (e.g., `label = "fg=blue bold"`)
+    pub roles: HashMap<String, String>,
+}
+
+impl Theme {
+    /// Loads a theme from a TOML file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the theme file
+    pub fn load<P: AsRef<Path>>(path: P) -> MotdResult<Theme> {
+        let contents = fs::read_to_string(path.as_ref())?;
+        let mut theme: Theme = toml::from_str(&contents)?;
+
+        if theme.name.is_empty() {
+            theme.name = path
+                .as_ref()
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+        }
+
+        Ok(theme)
+    }
+
+    /// Finds a theme, either by path or by name in one of the theme
+    /// directories (e.g., `solarized` loads `themes/solarized.toml`)
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of, or path to, the theme
+    pub fn find(name: &str) -> MotdResult<Theme> {
+        if Path::new(name).is_file() {
+            return Theme::load(name);
+        }
+
+        let path = THEME_DIRS
+            .iter()
+            .map(|dir| PathBuf::from(dir).join(format!("{}.toml", name)))
+            .find(|path| path.is_file());
+
+        match path {
+            Some(path) => Theme::load(path),
+            None => {
+                error!("theme {} not found", name);
+                Err(Error::CommandFailed)
+            }
+        }
+    }
+
+    /// Resolves a color by palette name, role name (using the role's
+    /// foreground color) or color value (e.g., `red` or `#ff8700`)
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Color to resolve
+    pub fn color(&self, name: &str) -> Option<Color> {
+        if let Some(color) = self.palette.get(name) {
+            return Color::parse(color);
+        }
+
+        if self.roles.contains_key(name) {
+            return self.resolve(name).fg;
+        }
+
+        Color::parse(name)
+    }
+
+    /// Resolves a style specification that may refer to roles and palette
+    /// colors, e.g. `label underline` or `fg=base03 bold`
+    ///
+    /// # Arguments
+    ///
+    /// * `spec` - Whitespace separated list of roles and attributes
+    pub fn resolve(&self, spec: &str) -> Style {
+        self.resolve_depth(spec, 0)
+    }
+
+    /// Resolves a style specification, following references to other roles
+    /// no deeper than `MAX_DEPTH`
+    fn resolve_depth(&self, spec: &str, depth: usize) -> Style {
+        let mut style = Style::default();
+
+        for attr in spec.split_whitespace() {
+            if let Some(role) = self.roles.get(attr) {
+                if depth < MAX_DEPTH {
+                    style.merge(&self.resolve_depth(role, depth + 1));
+                }
+                continue;
+            }
+
+            match attr.find('=') {
+                Some(i) => match self.palette.get(&attr[i + 1..]) {
+                    Some(color) => style.set(&format!("{}={}", &attr[..i], color)),
+                    None => style.set(attr),
+                },
+                None => style.set(attr),
+            }
+        }
+
+        style
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ansi::Color;
+
+    fn theme() -> Theme {
+        toml::from_str(
+            r##"
+            [palette]
+            base03 = "#002b36"
+            blue = "#268bd2"
+
+            [roles]
+            label = "fg=blue bold"
+            value = "label italic fg=base03"
+            loop = "loop underline"
+            "##,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn resolves_roles() {
+        let style = theme().resolve("value underline");
+        assert_eq!(style.fg, Some(Color::Rgb(0x00, 0x2b, 0x36)));
+        assert!(style.bold && style.italic && style.underline);
+        assert!(!style.dim);
+    }
+
+    #[test]
+    fn falls_back_to_standard_colors() {
+        let theme = theme();
+        assert_eq!(
+            theme.resolve("fg=blue").fg,
+            Some(Color::Rgb(0x26, 0x8b, 0xd2))
+        );
+        assert_eq!(theme.resolve("bg=red").bg, Some(Color::Indexed(1)));
+        assert_eq!(
+            Theme::default().resolve("fg=blue").fg,
+            Some(Color::Indexed(4))
+        );
+        assert!(theme.resolve("fg=base3 nope").is_plain());
+    }
+
+    #[test]
+    fn stops_at_recursive_roles() {
+        assert!(theme().resolve("loop").underline);
+    }
+
+    #[test]
+    fn names_themes_after_their_file() {
+        let path = std::env::temp_dir().join(format!("motd-theme-{}.toml", std::process::id()));
+        fs::write(&path, "[roles]\nlabel = \"bold\"\n").unwrap();
+        let theme = Theme::find(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let theme = theme.unwrap();
+        assert_eq!(theme.name, format!("motd-theme-{}", std::process::id()));
+        assert!(theme.resolve("label").bold);
+    }
+}
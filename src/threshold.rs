@@ -0,0 +1,185 @@
+//! Severity thresholds used to color values automatically.
+//!
+//! A threshold compares the typed value behind a command, not the text it
+//! renders, and can be declared in a template:
+//!
+//! ```text
+//! {{ disk(/) | threshold(warn=80, critical=95) }}
+//! {{ conns | threshold(field=established, warn=500) }}
+//! {{ uptime | threshold(below, warn=3600) }}
+//! ```
+//!
+//! or for every use of a command in the configuration file:
+//!
+//! ```toml
+//! [thresholds.disk]
+//! warn = 80
+//! critical = 95
+//! ```
+
+use crate::{template, value};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// How severe a value is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Normal,
+    Warn,
+    Critical,
+}
+
+/// Limits a value is compared against
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Threshold {
+    /// Path of the field to compare for values that aren't numbers (e.g.,
+    /// `established` for `conns`).  Defaults to `used_pct` if the value
+    /// has one
+    pub field: Option<String>,
+
+    /// Values past this are a warning
+    pub warn: Option<f64>,
+
+    /// Values past this are critical
+    pub critical: Option<f64>,
+
+    /// Values below (instead of above) the limits are flagged
+    pub below: bool,
+}
+
+impl Threshold {
+    /// Parses the arguments of a threshold filter, such as
+    /// `warn=80, critical=95` or `below, warn=3600`
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Comma-separated arguments of the filter
+    pub fn parse(args: &str) -> Threshold {
+        let mut threshold = Threshold::default();
+
+        for arg in template::split_top_level(args, ',')
+            .into_iter()
+            .map(str::trim)
+        {
+            let (key, val) = match arg.find('=') {
+                Some(i) => (arg[..i].trim(), arg[i + 1..].trim()),
+                None => (arg, ""),
+            };
+
+            match key {
+                "field" => threshold.field = Some(val.to_string()),
+                "warn" => threshold.warn = val.parse().ok(),
+                "critical" => threshold.critical = val.parse().ok(),
+                "below" => threshold.below = true,
+                _ => {}
+            }
+        }
+
+        threshold
+    }
+
+    /// Returns how severe a value is.  Values without a numeric value are
+    /// always normal
+    ///
+    /// # Arguments
+    ///
+    /// * `v` - Typed value to compare
+    pub fn severity(&self, v: &Value) -> Severity {
+        let field = match (&self.field, v) {
+            (Some(field), _) => value::lookup(v, field),
+            (None, Value::Object(map)) => map.get("used_pct"),
+            (None, v) => Some(v),
+        };
+
+        let n = match field.and_then(value::number) {
+            Some(n) => n,
+            None => return Severity::Normal,
+        };
+
+        let past = |limit: Option<f64>| match limit {
+            Some(limit) if self.below => n < limit,
+            Some(limit) => n > limit,
+            None => false,
+        };
+
+        if past(self.critical) {
+            Severity::Critical
+        } else if past(self.warn) {
+            Severity::Warn
+        } else {
+            Severity::Normal
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_arguments() {
+        let threshold = Threshold::parse(" below , warn = 3600, critical=60, field=\"a,b\", x=1");
+        assert!(threshold.below);
+        assert_eq!(threshold.warn, Some(3600.0));
+        assert_eq!(threshold.critical, Some(60.0));
+        assert_eq!(threshold.field.as_deref(), Some("\"a,b\""));
+
+        let threshold = Threshold::parse("warn=high");
+        assert_eq!(threshold.warn, None);
+        assert!(!threshold.below);
+    }
+
+    #[test]
+    fn compares_against_limits() {
+        let threshold = Threshold::parse("warn=80, critical=95");
+        assert_eq!(threshold.severity(&json!(80)), Severity::Normal);
+        assert_eq!(threshold.severity(&json!(80.5)), Severity::Warn);
+        assert_eq!(threshold.severity(&json!(95)), Severity::Warn);
+        assert_eq!(threshold.severity(&json!(96)), Severity::Critical);
+
+        let threshold = Threshold::parse("critical=95");
+        assert_eq!(threshold.severity(&json!(90)), Severity::Normal);
+        assert_eq!(threshold.severity(&json!(100)), Severity::Critical);
+    }
+
+    #[test]
+    fn compares_below_limits() {
+        let threshold = Threshold::parse("below, warn=3600, critical=60");
+        assert_eq!(threshold.severity(&json!(3600)), Severity::Normal);
+        assert_eq!(threshold.severity(&json!(3599)), Severity::Warn);
+        assert_eq!(threshold.severity(&json!(59)), Severity::Critical);
+    }
+
+    #[test]
+    fn compares_fields() {
+        let disk = json!({ "used_pct": 90.0, "free": 10 });
+        assert_eq!(Threshold::parse("warn=80").severity(&disk), Severity::Warn);
+        assert_eq!(
+            Threshold::parse("field=free, warn=5").severity(&disk),
+            Severity::Warn
+        );
+
+        let conns = json!({ "tcp": { "established": 501 }, "users": ["a", "b"] });
+        let threshold = Threshold::parse("field=tcp.established, warn=500");
+        assert_eq!(threshold.severity(&conns), Severity::Warn);
+        let threshold = Threshold::parse("field=users, warn=1");
+        assert_eq!(threshold.severity(&conns), Severity::Warn);
+        let threshold = Threshold::parse("field=missing, warn=1");
+        assert_eq!(threshold.severity(&conns), Severity::Normal);
+        assert_eq!(
+            Threshold::parse("warn=1").severity(&conns),
+            Severity::Normal
+        );
+    }
+
+    #[test]
+    fn ignores_non_numeric_values() {
+        let threshold = Threshold::parse("warn=1");
+        assert_eq!(threshold.severity(&json!("12")), Severity::Warn);
+        assert_eq!(threshold.severity(&json!("many")), Severity::Normal);
+        assert_eq!(threshold.severity(&json!(true)), Severity::Normal);
+        assert_eq!(threshold.severity(&Value::Null), Severity::Normal);
+    }
+}
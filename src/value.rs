@@ -0,0 +1,91 @@
+//! Helpers for working with typed fact values
+
+use serde_json::Value;
+
+/// Formats a typed value for display in a rendered template.  Strings are
+/// shown as-is, arrays are joined with commas, null is shown as nothing and
+/// objects are shown as JSON
+///
+/// # Arguments
+///
+/// * `value` - Value to format
+pub fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::Array(values) => values.iter().map(display).collect::<Vec<_>>().join(", "),
+        Value::Object(_) => value.to_string(),
+    }
+}
+
+/// Looks up a nested field of a value by a dot separated path (e.g.,
+/// `connections.established`).  Array elements are selected by index
+///
+/// # Arguments
+///
+/// * `value` - Value to search
+/// * `path` - Path of the field
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .filter(|key| !key.is_empty())
+        .try_fold(value, |value, key| match value {
+            Value::Object(map) => map.get(key),
+            Value::Array(values) => key.parse::<usize>().ok().and_then(|i| values.get(i)),
+            _ => None,
+        })
+}
+
+/// Returns the numeric value of a typed value, if it has one.  Arrays are
+/// measured by their length and strings are parsed as numbers
+///
+/// # Arguments
+///
+/// * `value` - Value to convert
+pub fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::Array(values) => Some(values.len() as f64),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn displays_values() {
+        assert_eq!(display(&Value::Null), "");
+        assert_eq!(display(&json!("a b")), "a b");
+        assert_eq!(display(&json!(1.5)), "1.5");
+        assert_eq!(display(&json!(["a", 1, null])), "a, 1, ");
+        assert_eq!(display(&json!({ "a": 1 })), "{\"a\":1}");
+    }
+
+    #[test]
+    fn looks_up_fields() {
+        let value = json!({ "a": { "b": [10, { "c": "x" }] } });
+        assert_eq!(lookup(&value, "a.b.0"), Some(&json!(10)));
+        assert_eq!(lookup(&value, "a.b.1.c"), Some(&json!("x")));
+        assert_eq!(lookup(&value, ""), Some(&value));
+        assert_eq!(lookup(&value, "a..b.0"), Some(&json!(10)));
+        assert_eq!(lookup(&value, "a.b.2"), None);
+        assert_eq!(lookup(&value, "a.b.x"), None);
+        assert_eq!(lookup(&value, "a.b.1.c.d"), None);
+        assert_eq!(lookup(&value, "z"), None);
+    }
+
+    #[test]
+    fn converts_to_numbers() {
+        assert_eq!(number(&json!(3)), Some(3.0));
+        assert_eq!(number(&json!([1, 2])), Some(2.0));
+        assert_eq!(number(&json!(" 4.5 ")), Some(4.5));
+        assert_eq!(number(&json!("four")), None);
+        assert_eq!(number(&json!({})), None);
+        assert_eq!(number(&Value::Null), None);
+    }
+}
@@ -0,0 +1,171 @@
+//! Widgets that draw values graphically, such as progress bars.
+//!
+//! Widgets take a reference to the value to draw, followed by options:
+//!
+//! ```text
+//! {{ bar(mem.used_pct, width=30) }}
+//! {{ gauge(disk("/").used_pct, warn=70, critical=85) }}
+//! {{ bar(cpu, ascii) }}
+//! ```
+//!
+//! * `width=N` - Number of cells the bar is drawn with
+//! * `ascii` - Draw the bar with ASCII characters, even if the terminal
+//!   supports Unicode
+//! * `warn=N`, `critical=N` and `below` - Limits at which the bar changes
+//!   color, as for the `threshold` filter
+
+use crate::{
+    template::{self, ValueRef},
+    threshold::Threshold,
+};
+
+/// Number of cells in a bar if no width is given
+pub const BAR_WIDTH: usize = 20;
+
+/// Number of cells in a gauge if no width is given
+pub const GAUGE_WIDTH: usize = 10;
+
+/// Threshold used when neither the widget nor the configuration has one
+pub const DEFAULT_THRESHOLD: Threshold = Threshold {
+    field: None,
+    warn: Some(80.0),
+    critical: Some(90.0),
+    below: false,
+};
+
+/// Partially filled blocks, from one eighth to seven eighths of a cell
+const EIGHTHS: [char; 7] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+/// Arguments of a widget
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Value to draw
+    pub value: ValueRef,
+
+    /// Number of cells to draw, if given
+    pub width: Option<usize>,
+
+    /// Draw with ASCII characters only
+    pub ascii: bool,
+
+    /// Limits given for coloring, if any
+    pub threshold: Option<Threshold>,
+}
+
+impl Options {
+    /// Parses the arguments of a widget, returning None if they don't
+    /// start with a reference to a value
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Arguments passed to the widget, exactly as written
+    pub fn parse(args: &str) -> Option<Options> {
+        let mut args = template::split_top_level(args, ',').into_iter();
+        let value = template::parse_ref(args.next()?)?;
+
+        let mut width = None;
+        let mut ascii = false;
+        let mut limits = Vec::new();
+        for arg in args.map(str::trim) {
+            match arg {
+                "ascii" => ascii = true,
+                arg if arg.starts_with("width=") => width = arg[6..].trim().parse().ok(),
+                arg => limits.push(arg),
+            }
+        }
+
+        let threshold = if limits.is_empty() {
+            None
+        } else {
+            Some(Threshold::parse(&limits.join(",")))
+        };
+
+        Some(Options {
+            value,
+            width,
+            ascii,
+            threshold,
+        })
+    }
+}
+
+/// Draws a bar filled to a percentage, returning the filled and empty
+/// parts separately so they can be styled differently
+///
+/// # Arguments
+///
+/// * `pct` - Percentage to fill, clamped to 0-100
+/// * `width` - Number of cells in the bar
+/// * `unicode` - Draw with block characters, which can fill eighths of
+///   a cell, instead of ASCII
+pub fn bar(pct: f64, width: usize, unicode: bool) -> (String, String) {
+    let fraction = pct.clamp(0.0, 100.0) / 100.0;
+
+    if !unicode {
+        let full = (fraction * width as f64).round() as usize;
+        return ("#".repeat(full), "-".repeat(width - full));
+    }
+
+    let eighths = (fraction * (width * 8) as f64).round() as usize;
+    let (full, partial) = (eighths / 8, eighths % 8);
+    let mut filled = "█".repeat(full);
+    let mut cells = full;
+    if partial > 0 {
+        filled.push(EIGHTHS[partial - 1]);
+        cells += 1;
+    }
+
+    (filled, "░".repeat(width - cells))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar_of(pct: f64, width: usize, unicode: bool) -> String {
+        let (filled, empty) = bar(pct, width, unicode);
+        format!("{}|{}", filled, empty)
+    }
+
+    #[test]
+    fn fills_bars() {
+        assert_eq!(bar_of(0.0, 4, true), "|░░░░");
+        assert_eq!(bar_of(0.0, 4, false), "|----");
+        assert_eq!(bar_of(100.0, 4, true), "████|");
+        assert_eq!(bar_of(100.0, 4, false), "####|");
+        assert_eq!(bar_of(150.0, 4, true), "████|");
+        assert_eq!(bar_of(-5.0, 4, false), "|----");
+        assert_eq!(bar_of(0.0, 0, true), "|");
+    }
+
+    #[test]
+    fn rounds_to_nearest_cell() {
+        assert_eq!(bar_of(50.0, 3, false), "##|-");
+        assert_eq!(bar_of(49.0, 3, false), "#|--");
+        assert_eq!(bar_of(10.0, 4, true), "▍|░░░");
+        assert_eq!(bar_of(1.0, 10, true), "▏|░░░░░░░░░");
+        assert_eq!(bar_of(0.1, 10, true), "|░░░░░░░░░░");
+        assert_eq!(bar_of(99.9, 4, true), "████|");
+        assert_eq!(bar_of(60.0, 2, true), "█▎|");
+    }
+
+    #[test]
+    fn parses_options() {
+        let opts =
+            Options::parse(r#"disk("/a, b").used_pct, width=30, ascii, below, warn=10"#).unwrap();
+        assert_eq!(opts.value.call.name, "disk");
+        assert_eq!(opts.value.call.args.as_deref(), Some(r#""/a, b""#));
+        assert_eq!(opts.value.field.as_deref(), Some("used_pct"));
+        assert_eq!(opts.width, Some(30));
+        assert!(opts.ascii);
+
+        let threshold = opts.threshold.unwrap();
+        assert!(threshold.below);
+        assert_eq!(threshold.warn, Some(10.0));
+
+        let opts = Options::parse("cpu").unwrap();
+        assert!(opts.width.is_none() && !opts.ascii && opts.threshold.is_none());
+        assert!(Options::parse("").is_none());
+        assert!(Options::parse("(1)").is_none());
+    }
+}